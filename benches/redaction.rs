@@ -0,0 +1,60 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use greentic_telemetry::redaction::{self, RedactionMode, RedactionPolicy};
+use regex::Regex;
+
+const CLEAN: &str = "GET /api/v1/flows/intake/nodes/parse completed in 12ms";
+const EMAIL: &str = "password reset sent to alice@example.com for flow intake";
+const JSON: &str = r#"{"span":"tool.call","attributes":{"tool":"search","query":"flights to Lisbon","user":{"email":"alice@example.com"}}}"#;
+
+/// The previous engine: one `replace_all` and allocation per pattern,
+/// whether or not anything matched.
+struct Baseline(Vec<Regex>);
+
+impl Baseline {
+    fn new() -> Self {
+        Self(
+            [
+                r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}",
+                r"(?i)bearer\s+[a-z0-9._\-]+\b",
+                r"(?i)(api[-_]?key|token)\s*[:=]\s*[a-z0-9._\-]+\b",
+                r"\+?\d[\d\-\s]{7,14}\d",
+            ]
+            .into_iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect(),
+        )
+    }
+
+    fn redact(&self, value: &str) -> String {
+        let mut output = value.to_string();
+        for regex in &self.0 {
+            output = regex.replace_all(&output, "[REDACTED]").into_owned();
+        }
+        output
+    }
+}
+
+fn redaction(c: &mut Criterion) {
+    let baseline = Baseline::new();
+    let policy = RedactionPolicy::new(RedactionMode::Strict);
+    redaction::set_policies(policy.clone().into());
+
+    let mut group = c.benchmark_group("redact");
+    for (name, value) in [("clean", CLEAN), ("email", EMAIL), ("json", JSON)] {
+        group.bench_function(format!("baseline/{name}"), |b| {
+            b.iter(|| baseline.redact(black_box(value)))
+        });
+        group.bench_function(format!("policy/{name}"), |b| {
+            b.iter(|| policy.redact(black_box("note"), black_box(value)))
+        });
+        group.bench_function(format!("installed/{name}"), |b| {
+            b.iter(|| redaction::redact(black_box("note"), black_box(value)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, redaction);
+criterion_main!(benches);
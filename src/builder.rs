@@ -0,0 +1,842 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+#[cfg(feature = "otlp")]
+use opentelemetry::{KeyValue, global, trace::TracerProvider as _};
+#[cfg(feature = "otlp")]
+use opentelemetry_otlp::{
+    LogExporter, MetricExporter, SpanExporter, WithExportConfig, WithHttpConfig, WithTonicConfig,
+    tonic_types::metadata::MetadataMap,
+};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    logs::SdkLoggerProvider,
+    metrics::SdkMeterProvider,
+    resource::Resource,
+    trace::{
+        BatchSpanProcessor, Sampler, SdkTracerProvider, Span, SpanData, SpanProcessor,
+        TracerProviderBuilder,
+    },
+};
+#[cfg(feature = "otlp")]
+use std::{collections::HashMap, time::Duration};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    EnvFilter, Registry,
+    fmt::{
+        self,
+        format::{DefaultFields, PrettyFields},
+    },
+    layer::Layer,
+    prelude::*,
+};
+
+use crate::export::ExportConfig;
+#[cfg(feature = "otlp")]
+use crate::export::ExportMode;
+use crate::init::TelemetryState;
+#[cfg(feature = "otlp")]
+use crate::json_stdout::{JsonStdoutLogExporter, JsonStdoutMetricExporter, JsonStdoutSpanExporter};
+use crate::layer::layer_from_task_local;
+#[cfg(feature = "otlp")]
+use crate::layer::tracer_from_task_local;
+#[cfg(feature = "otlp")]
+use crate::logs::{ContextLogProcessor, bridge_layer};
+#[cfg(feature = "otlp")]
+use crate::metrics::{self, MetricView};
+#[cfg(feature = "otlp")]
+use crate::prometheus::PrometheusServer;
+#[cfg(feature = "otlp")]
+use crate::propagation::{self, BaggagePolicy, Propagator};
+use crate::redaction::{
+    self, RedactingFields, RedactingFormat, RedactionPolicies, RedactionPolicy,
+};
+#[cfg(feature = "otlp")]
+use crate::redaction::{RedactingSpanProcessor, RedactionLogProcessor};
+#[cfg(feature = "otlp")]
+use crate::sampling::{
+    TailSamplingConfig, TailSamplingProcessor, TenantRateConfig, TenantRateSampler,
+};
+#[cfg(feature = "otlp")]
+use crate::span_metrics::{SpanMetricsConfig, SpanMetricsProcessor};
+
+/// A layer that can be stacked directly onto the registry.
+pub type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Human-readable output written to stdout alongside the exporters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FmtStyle {
+    Compact,
+    Pretty,
+    Json,
+}
+
+/// Composes service identity, exporters, sampling, filtering and layers into a
+/// single global telemetry installation.
+///
+/// ```no_run
+/// use greentic_telemetry::{FmtStyle, TelemetryBuilder};
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let _guard = TelemetryBuilder::new("greentic-runner")
+///     .with_service_version(env!("CARGO_PKG_VERSION"))
+///     .with_otlp_endpoint("http://localhost:4317")
+///     .with_fmt(FmtStyle::Json)
+///     .install()?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+pub struct TelemetryBuilder {
+    service_name: String,
+    service_version: Option<String>,
+    deployment_env: Option<String>,
+    export: Option<ExportConfig>,
+    #[cfg(feature = "otlp")]
+    trace_pipeline: TracePipeline,
+    #[cfg(feature = "otlp")]
+    propagators: Option<Vec<Propagator>>,
+    #[cfg(feature = "otlp")]
+    baggage_policy: Option<BaggagePolicy>,
+    #[cfg(feature = "otlp")]
+    metric_views: Vec<MetricView>,
+    #[cfg(feature = "otlp")]
+    prometheus: Option<String>,
+    redaction_policies: Option<RedactionPolicies>,
+    strict_redaction: Option<bool>,
+    filter: Option<String>,
+    fmt: Option<FmtStyle>,
+    thread_ids: bool,
+    log_dir: Option<PathBuf>,
+    context_layer: bool,
+    keep_existing_subscriber: bool,
+    extra_layers: Vec<BoxedLayer>,
+}
+
+impl TelemetryBuilder {
+    pub fn new(service_name: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+            service_version: None,
+            deployment_env: None,
+            export: None,
+            #[cfg(feature = "otlp")]
+            trace_pipeline: TracePipeline::default(),
+            #[cfg(feature = "otlp")]
+            propagators: None,
+            #[cfg(feature = "otlp")]
+            baggage_policy: None,
+            #[cfg(feature = "otlp")]
+            metric_views: Vec::new(),
+            #[cfg(feature = "otlp")]
+            prometheus: None,
+            redaction_policies: None,
+            strict_redaction: None,
+            filter: None,
+            fmt: None,
+            thread_ids: false,
+            log_dir: None,
+            context_layer: true,
+            keep_existing_subscriber: false,
+            extra_layers: Vec::new(),
+        }
+    }
+
+    pub fn with_service_version(mut self, version: impl Into<String>) -> Self {
+        self.service_version = Some(version.into());
+        self
+    }
+
+    pub fn with_deployment_env(mut self, env: impl Into<String>) -> Self {
+        self.deployment_env = Some(env.into());
+        self
+    }
+
+    /// Export spans and metrics over OTLP gRPC to `endpoint`.
+    pub fn with_otlp_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.export = Some(ExportConfig::otlp_grpc(endpoint));
+        self
+    }
+
+    /// Also serve metrics for Prometheus to scrape on `addr`, e.g.
+    /// `0.0.0.0:9464`, next to the configured export. Without one, spans and
+    /// logs are not exported.
+    #[cfg(feature = "otlp")]
+    pub fn with_prometheus(mut self, addr: impl Into<String>) -> Self {
+        self.prometheus = Some(addr.into());
+        self
+    }
+
+    /// Export over OTLP gRPC only when `OTEL_EXPORTER_OTLP_ENDPOINT` is set.
+    pub fn with_otlp_endpoint_from_env(self) -> Self {
+        match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+            Ok(endpoint) => self.with_otlp_endpoint(endpoint),
+            Err(_) => self,
+        }
+    }
+
+    /// Export according to `config`, including its sampling strategy unless
+    /// [`with_sampler`](Self::with_sampler) or
+    /// [`with_sampling_ratio`](Self::with_sampling_ratio) is also called, in
+    /// either order.
+    ///
+    /// Pair with [`ExportConfig::from_env`] to honour `TELEMETRY_EXPORT`,
+    /// `OTLP_ENDPOINT`, `OTLP_HEADERS`, `TELEMETRY_SAMPLING` and `CLOUD_PRESET`.
+    pub fn with_export_config(mut self, config: ExportConfig) -> Self {
+        #[cfg(feature = "otlp")]
+        {
+            if config.tail_sampling.is_some() {
+                self.trace_pipeline.tail_sampling = config.tail_sampling.clone();
+            }
+            if config.tenant_rate.is_some() {
+                self.trace_pipeline.tenant_rate = config.tenant_rate.clone();
+            }
+            if config.span_metrics.is_some() {
+                self.trace_pipeline.span_metrics = config.span_metrics.clone();
+            }
+        }
+        self.export = Some(config);
+        self
+    }
+
+    #[cfg(feature = "otlp")]
+    pub fn with_sampler(mut self, sampler: Sampler) -> Self {
+        self.trace_pipeline.sampler = Some(sampler);
+        self
+    }
+
+    /// Sample root traces at `ratio`, following the parent decision otherwise.
+    ///
+    /// Ratios at or below zero (and NaN) sample nothing; ratios at or above
+    /// one sample everything.
+    #[cfg(feature = "otlp")]
+    pub fn with_sampling_ratio(self, ratio: f64) -> Self {
+        self.with_sampler(Sampler::ParentBased(Box::new(root_ratio_sampler(ratio))))
+    }
+
+    /// Cap root traces per second for each `gt.tenant`, on top of the
+    /// configured sampler.
+    #[cfg(feature = "otlp")]
+    pub fn with_tenant_rate_limit(mut self, config: TenantRateConfig) -> Self {
+        self.trace_pipeline.tenant_rate = Some(config);
+        self
+    }
+
+    /// Buffer spans per trace and export only traces matching `config`.
+    #[cfg(feature = "otlp")]
+    pub fn with_tail_sampling(mut self, config: TailSamplingConfig) -> Self {
+        self.trace_pipeline.tail_sampling = Some(config);
+        self
+    }
+
+    /// Record request, error and duration metrics for finished spans
+    /// matching `config`, before sampling decisions made at export.
+    #[cfg(feature = "otlp")]
+    pub fn with_span_metrics(mut self, config: SpanMetricsConfig) -> Self {
+        self.trace_pipeline.span_metrics = Some(config);
+        self
+    }
+
+    /// Add a span processor that runs ahead of the exporting processor.
+    #[cfg(feature = "otlp")]
+    pub fn with_span_processor(mut self, processor: impl SpanProcessor + 'static) -> Self {
+        self.trace_pipeline.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Trace context formats for `inject_carrier`/`extract_carrier`. Defaults
+    /// to `OTEL_PROPAGATORS`, or `tracecontext,baggage` when unset.
+    #[cfg(feature = "otlp")]
+    pub fn with_propagators(mut self, propagators: impl IntoIterator<Item = Propagator>) -> Self {
+        self.propagators = Some(propagators.into_iter().collect());
+        self
+    }
+
+    /// Which `gt.*` keys travel in the `baggage` header, and size limits.
+    /// Defaults to [`BaggagePolicy::from_env`].
+    #[cfg(feature = "otlp")]
+    pub fn with_baggage_policy(mut self, policy: BaggagePolicy) -> Self {
+        self.baggage_policy = Some(policy);
+        self
+    }
+
+    /// Reshape the metric streams of matching instruments, e.g. with
+    /// bucket boundaries; the first view matching an instrument applies.
+    #[cfg(feature = "otlp")]
+    pub fn with_metric_view(mut self, view: MetricView) -> Self {
+        self.metric_views.push(view);
+        self
+    }
+
+    /// Redaction applied to exported spans, logs, metrics and fmt output,
+    /// for every tenant. Defaults to [`RedactionPolicies::from_env`].
+    pub fn with_redaction_policy(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction_policies = Some(policy.into());
+        self
+    }
+
+    /// Like [`TelemetryBuilder::with_redaction_policy`], with a policy per
+    /// tenant. Swap them later with [`redaction::set_policies`].
+    pub fn with_redaction_policies(mut self, policies: RedactionPolicies) -> Self {
+        self.redaction_policies = Some(policies);
+        self
+    }
+
+    /// Make [`TelemetryBuilder::install`] fail with a
+    /// [`redaction::RedactionConfigError`] on invalid `PII_*` settings or a
+    /// failing redaction self-test, instead of logging and carrying on.
+    /// Defaults to `PII_STRICT_CONFIG`.
+    pub fn with_strict_redaction(mut self, strict: bool) -> Self {
+        self.strict_redaction = Some(strict);
+        self
+    }
+
+    /// Filter directives used when `RUST_LOG` is not set. Defaults to `info`.
+    pub fn with_filter(mut self, directives: impl Into<String>) -> Self {
+        self.filter = Some(directives.into());
+        self
+    }
+
+    pub fn with_fmt(mut self, style: FmtStyle) -> Self {
+        self.fmt = Some(style);
+        self
+    }
+
+    /// Include thread IDs in the fmt output.
+    pub fn with_thread_ids(mut self, enabled: bool) -> Self {
+        self.thread_ids = enabled;
+        self
+    }
+
+    /// Additionally write JSON logs to a daily rolling file in `dir`.
+    pub fn with_log_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.log_dir = Some(dir.into());
+        self
+    }
+
+    /// Install the providers but leave an already-set global subscriber in
+    /// place instead of failing, as `init_telemetry` always has.
+    pub(crate) fn keep_existing_subscriber(mut self) -> Self {
+        self.keep_existing_subscriber = true;
+        self
+    }
+
+    /// Toggle the task-local `TelemetryCtx` layer (enabled by default).
+    pub fn with_context_layer(mut self, enabled: bool) -> Self {
+        self.context_layer = enabled;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: impl Layer<Registry> + Send + Sync + 'static) -> Self {
+        self.extra_layers.push(Box::new(layer));
+        self
+    }
+
+    pub fn with_layers(mut self, layers: impl IntoIterator<Item = BoxedLayer>) -> Self {
+        self.extra_layers.extend(layers);
+        self
+    }
+
+    /// Install the global subscriber and providers.
+    ///
+    /// Fails if a global `tracing` subscriber has already been set.
+    pub fn install(self) -> Result<TelemetryGuard> {
+        if tracing::dispatcher::has_been_set() && !self.keep_existing_subscriber {
+            return Err(anyhow!("a global tracing subscriber is already installed"));
+        }
+
+        let filter = EnvFilter::try_from_default_env()
+            .or_else(|_| EnvFilter::try_new(self.filter.as_deref().unwrap_or("info")))?;
+
+        let strict_redaction = self
+            .strict_redaction
+            .unwrap_or_else(redaction::strict_from_env);
+        match self.redaction_policies {
+            Some(policies) if strict_redaction => {
+                redaction::self_test()?;
+                redaction::init_with_policies(policies);
+            }
+            Some(policies) => {
+                redaction::init_with_policies(policies);
+            }
+            None if strict_redaction => {
+                redaction::try_init_from_env()?;
+            }
+            None => redaction::init_from_env(),
+        }
+
+        let mut guard = TelemetryGuard::default();
+        let mut layers: Vec<BoxedLayer> = Vec::new();
+
+        if self.context_layer {
+            layers.push(Box::new(layer_from_task_local()));
+        }
+        layers.extend(self.extra_layers);
+
+        #[cfg(feature = "otlp")]
+        {
+            for view in &self.metric_views {
+                view.validate()?;
+            }
+            let propagators = match self.propagators {
+                Some(propagators) => propagators,
+                None => Propagator::from_env()?,
+            };
+            global::set_text_map_propagator(propagation::composite(&propagators));
+            if let Some(policy) = self.baggage_policy {
+                propagation::set_baggage_policy(policy);
+            }
+
+            let prometheus = self.prometheus.or_else(|| {
+                let export = self.export.as_ref()?;
+                export.prometheus.clone()
+            });
+            if self.export.is_some() || prometheus.is_some() {
+                let resource = build_resource(
+                    &self.service_name,
+                    self.service_version.as_deref(),
+                    self.deployment_env.as_deref(),
+                );
+                let providers = install_exporters(
+                    self.export.as_ref(),
+                    prometheus.as_deref(),
+                    resource,
+                    self.trace_pipeline,
+                    self.metric_views,
+                )?;
+
+                let tracer = providers.tracer.tracer("greentic-telemetry");
+                if self.context_layer {
+                    let tracer = tracer_from_task_local(tracer);
+                    layers.push(Box::new(tracing_opentelemetry::layer().with_tracer(tracer)));
+                } else {
+                    layers.push(Box::new(tracing_opentelemetry::layer().with_tracer(tracer)));
+                }
+                layers.push(bridge_layer(&providers.logger));
+
+                guard.providers = Some(providers);
+            }
+        }
+
+        #[cfg(not(feature = "otlp"))]
+        if let Some(export) = &self.export {
+            tracing::warn!(
+                service = %self.service_name,
+                mode = ?export.mode,
+                "otlp feature disabled; ignoring export configuration"
+            );
+        }
+
+        if let Some(style) = self.fmt {
+            let layer = fmt::layer()
+                .with_target(true)
+                .with_thread_ids(self.thread_ids);
+            layers.push(match style {
+                FmtStyle::Compact => layer
+                    .compact()
+                    .fmt_fields(RedactingFields::new(DefaultFields::new()))
+                    .boxed(),
+                FmtStyle::Pretty => layer
+                    .pretty()
+                    .fmt_fields(RedactingFields::new(PrettyFields::new()))
+                    .with_ansi(atty::is(atty::Stream::Stdout))
+                    .boxed(),
+                FmtStyle::Json => layer
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .map_event_format(RedactingFormat::new)
+                    .boxed(),
+            });
+        }
+
+        if let Some(dir) = &self.log_dir {
+            let appender =
+                tracing_appender::rolling::daily(dir, format!("{}.log", self.service_name));
+            let (writer, worker) = tracing_appender::non_blocking(appender);
+            layers.push(
+                fmt::layer()
+                    .with_writer(writer)
+                    .with_ansi(false)
+                    .json()
+                    .map_event_format(RedactingFormat::new)
+                    .boxed(),
+            );
+            guard.workers.push(worker);
+        }
+
+        let installed = Registry::default().with(layers).with(filter).try_init();
+        if !self.keep_existing_subscriber {
+            installed?;
+        }
+
+        crate::init::mark_installed(TelemetryState {
+            service_name: self.service_name,
+            service_version: self.service_version,
+            deployment_env: self.deployment_env,
+        });
+        Ok(guard)
+    }
+}
+
+/// Flushes and shuts down the installed providers when dropped.
+#[derive(Default)]
+#[must_use = "dropping the guard shuts telemetry down; call `detach` to keep it running"]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otlp")]
+    providers: Option<Providers>,
+    workers: Vec<WorkerGuard>,
+}
+
+impl TelemetryGuard {
+    /// Address the Prometheus endpoint listens on, when serving metrics to
+    /// Prometheus; useful after binding port 0.
+    #[cfg(feature = "otlp")]
+    pub fn prometheus_addr(&self) -> Option<std::net::SocketAddr> {
+        let providers = self.providers.as_ref()?;
+        providers.prometheus.as_ref().map(PrometheusServer::addr)
+    }
+
+    /// Keep the pipeline running for the rest of the process; use
+    /// [`crate::shutdown`] to flush it instead.
+    pub fn detach(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(providers) = self.providers.take() {
+            providers.shutdown();
+        }
+    }
+}
+
+/// Sampling and processing stages applied to spans ahead of export.
+#[cfg(feature = "otlp")]
+#[derive(Default)]
+struct TracePipeline {
+    /// Set explicitly; takes precedence over the export config's sampling.
+    sampler: Option<Sampler>,
+    tenant_rate: Option<TenantRateConfig>,
+    tail_sampling: Option<TailSamplingConfig>,
+    span_metrics: Option<SpanMetricsConfig>,
+    processors: Vec<Box<dyn SpanProcessor>>,
+}
+
+/// Adapts a boxed processor to the SDK's generic `with_span_processor`.
+#[cfg(feature = "otlp")]
+#[derive(Debug)]
+struct DynSpanProcessor(Box<dyn SpanProcessor>);
+
+#[cfg(feature = "otlp")]
+impl SpanProcessor for DynSpanProcessor {
+    fn on_start(&self, span: &mut Span, cx: &opentelemetry::Context) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        self.0.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource);
+    }
+}
+
+/// The SDK providers backing one installation.
+#[cfg(feature = "otlp")]
+#[derive(Clone)]
+pub(crate) struct Providers {
+    pub(crate) tracer: SdkTracerProvider,
+    pub(crate) meter: SdkMeterProvider,
+    pub(crate) logger: SdkLoggerProvider,
+    pub(crate) prometheus: Option<PrometheusServer>,
+}
+
+#[cfg(feature = "otlp")]
+impl Providers {
+    pub(crate) fn shutdown(&self) {
+        let _ = self.tracer.shutdown();
+        let _ = self.meter.shutdown();
+        let _ = self.logger.shutdown();
+        if let Some(server) = &self.prometheus {
+            server.shutdown();
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn build_resource(
+    service_name: &str,
+    service_version: Option<&str>,
+    deployment_env: Option<&str>,
+) -> Resource {
+    let mut attributes = Vec::new();
+    if let Some(version) = service_version {
+        attributes.push(KeyValue::new("service.version", version.to_string()));
+    }
+    if let Some(env) = deployment_env {
+        attributes.push(KeyValue::new("deployment.environment", env.to_string()));
+    }
+
+    Resource::builder()
+        .with_service_name(service_name.to_string())
+        .with_attributes(attributes)
+        .build()
+}
+
+#[cfg(feature = "otlp")]
+fn install_exporters(
+    export: Option<&ExportConfig>,
+    prometheus: Option<&str>,
+    resource: Resource,
+    trace_pipeline: TracePipeline,
+    metric_views: Vec<MetricView>,
+) -> Result<Providers> {
+    let TracePipeline {
+        sampler,
+        tenant_rate,
+        tail_sampling,
+        span_metrics,
+        mut processors,
+    } = trace_pipeline;
+    if let Some(config) = span_metrics {
+        processors.push(Box::new(SpanMetricsProcessor::new(config)?));
+    }
+    let sampler = effective_sampler(sampler, export);
+    let tracer_builder = SdkTracerProvider::builder().with_resource(resource.clone());
+    let tracer_builder = match tenant_rate {
+        Some(config) => tracer_builder.with_sampler(TenantRateSampler::new(config, sampler)),
+        None => tracer_builder.with_sampler(sampler),
+    };
+    let tracer_builder = processors
+        .into_iter()
+        .fold(tracer_builder, |builder, processor| {
+            builder.with_span_processor(DynSpanProcessor(processor))
+        });
+    let export_spans = |builder: TracerProviderBuilder, processor: BatchSpanProcessor| {
+        let processor = RedactingSpanProcessor::new(processor);
+        match tail_sampling {
+            Some(config) => {
+                builder.with_span_processor(TailSamplingProcessor::new(config, processor))
+            }
+            None => builder.with_span_processor(processor),
+        }
+    };
+    let meter_builder = metrics::with_views(
+        SdkMeterProvider::builder().with_resource(resource.clone()),
+        metric_views,
+    );
+    // Context attributes must be stamped, and then masked, before the
+    // exporting processor copies the record.
+    let logger_builder = SdkLoggerProvider::builder()
+        .with_resource(resource)
+        .with_log_processor(ContextLogProcessor)
+        .with_log_processor(RedactionLogProcessor);
+
+    let (tracer_builder, mut meter_builder, logger_builder) = match export {
+        None => (tracer_builder, meter_builder, logger_builder),
+        Some(export) if export.mode == ExportMode::OtlpGrpc => {
+            let metadata = grpc_metadata(&export.headers)?;
+            let mut spans = SpanExporter::builder()
+                .with_tonic()
+                .with_metadata(metadata.clone());
+            let mut metrics = MetricExporter::builder()
+                .with_tonic()
+                .with_metadata(metadata.clone());
+            let mut logs = LogExporter::builder().with_tonic().with_metadata(metadata);
+            if let Some(endpoint) = &export.endpoint {
+                spans = spans.with_endpoint(endpoint.clone());
+                metrics = metrics.with_endpoint(endpoint.clone());
+                logs = logs.with_endpoint(endpoint.clone());
+            }
+            (
+                export_spans(
+                    tracer_builder,
+                    BatchSpanProcessor::builder(spans.build()?).build(),
+                ),
+                meter_builder.with_periodic_exporter(metrics.build()?),
+                logger_builder.with_batch_exporter(logs.build()?),
+            )
+        }
+        Some(export) if export.mode == ExportMode::OtlpHttp => {
+            let mut spans = SpanExporter::builder()
+                .with_http()
+                .with_headers(export.headers.clone());
+            let mut metrics = MetricExporter::builder()
+                .with_http()
+                .with_headers(export.headers.clone());
+            let mut logs = LogExporter::builder()
+                .with_http()
+                .with_headers(export.headers.clone());
+            if let Some(endpoint) = &export.endpoint {
+                spans = spans.with_endpoint(signal_endpoint(endpoint, "/v1/traces"));
+                metrics = metrics.with_endpoint(signal_endpoint(endpoint, "/v1/metrics"));
+                logs = logs.with_endpoint(signal_endpoint(endpoint, "/v1/logs"));
+            }
+            (
+                export_spans(
+                    tracer_builder,
+                    BatchSpanProcessor::builder(spans.build()?).build(),
+                ),
+                meter_builder.with_periodic_exporter(metrics.build()?),
+                logger_builder.with_batch_exporter(logs.build()?),
+            )
+        }
+        // ExportMode::JsonStdout
+        Some(_) => (
+            export_spans(
+                tracer_builder,
+                BatchSpanProcessor::builder(JsonStdoutSpanExporter::default()).build(),
+            ),
+            meter_builder.with_periodic_exporter(JsonStdoutMetricExporter),
+            logger_builder.with_batch_exporter(JsonStdoutLogExporter::default()),
+        ),
+    };
+    let prometheus = match prometheus {
+        Some(addr) => {
+            let (server, reader) = PrometheusServer::serve(addr)?;
+            meter_builder = meter_builder.with_reader(reader);
+            Some(server)
+        }
+        None => None,
+    };
+
+    let providers = Providers {
+        tracer: tracer_builder.build(),
+        meter: meter_builder.build(),
+        logger: logger_builder.build(),
+        prometheus,
+    };
+
+    global::set_tracer_provider(providers.tracer.clone());
+    global::set_meter_provider(providers.meter.clone());
+    crate::init::register_providers(providers.clone());
+
+    Ok(providers)
+}
+
+/// Convert `OTLP_HEADERS` pairs into gRPC metadata; keys are lowercased as
+/// required by HTTP/2.
+#[cfg(feature = "otlp")]
+fn grpc_metadata(headers: &HashMap<String, String>) -> Result<MetadataMap> {
+    let mut map = http::HeaderMap::with_capacity(headers.len());
+    for (key, value) in headers {
+        let name = http::HeaderName::from_bytes(key.to_ascii_lowercase().as_bytes())
+            .map_err(|err| anyhow!("invalid OTLP header name '{key}': {err}"))?;
+        let value = http::HeaderValue::from_str(value)
+            .map_err(|err| anyhow!("invalid OTLP header value for '{key}': {err}"))?;
+        map.insert(name, value);
+    }
+    Ok(MetadataMap::from_headers(map))
+}
+
+/// The explicitly set sampler, else the export config's, else parent-based
+/// always-on.
+#[cfg(feature = "otlp")]
+fn effective_sampler(explicit: Option<Sampler>, export: Option<&ExportConfig>) -> Sampler {
+    explicit
+        .or_else(|| export.map(|export| export.sampling.into_sampler()))
+        .unwrap_or_else(|| Sampler::ParentBased(Box::new(Sampler::AlwaysOn)))
+}
+
+/// Root sampler for a ratio: nothing at or below zero (or NaN), everything at
+/// or above one.
+#[cfg(feature = "otlp")]
+fn root_ratio_sampler(ratio: f64) -> Sampler {
+    if ratio.is_nan() || ratio <= 0.0 {
+        Sampler::AlwaysOff
+    } else if ratio >= 1.0 {
+        Sampler::AlwaysOn
+    } else {
+        Sampler::TraceIdRatioBased(ratio)
+    }
+}
+
+/// Append the per-signal path to a collector base URL unless already present.
+#[cfg(feature = "otlp")]
+fn signal_endpoint(base: &str, path: &str) -> String {
+    let base = base.trim_end_matches('/');
+    if base.ends_with(path) {
+        base.to_string()
+    } else {
+        format!("{base}{path}")
+    }
+}
+
+#[cfg(all(test, feature = "otlp"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_endpoint_appends_path_once() {
+        assert_eq!(
+            signal_endpoint("http://collector:4318", "/v1/traces"),
+            "http://collector:4318/v1/traces"
+        );
+        assert_eq!(
+            signal_endpoint("http://collector:4318/v1/traces", "/v1/traces"),
+            "http://collector:4318/v1/traces"
+        );
+    }
+
+    #[test]
+    fn sampling_ratio_clamps_out_of_range_values() {
+        let kind = |ratio: f64| format!("{:?}", root_ratio_sampler(ratio));
+        assert_eq!(kind(0.0), "AlwaysOff");
+        assert_eq!(kind(-0.5), "AlwaysOff");
+        assert_eq!(kind(f64::NAN), "AlwaysOff");
+        assert_eq!(kind(1.0), "AlwaysOn");
+        assert_eq!(kind(3.0), "AlwaysOn");
+        assert_eq!(kind(0.25), "TraceIdRatioBased(0.25)");
+    }
+
+    #[test]
+    fn explicit_sampler_wins_over_export_config_in_either_order() {
+        let mut config = ExportConfig::json_default();
+        config.sampling = crate::export::Sampling::TraceIdRatio(0.5);
+        let sampler = |builder: TelemetryBuilder| {
+            format!(
+                "{:?}",
+                effective_sampler(builder.trace_pipeline.sampler, builder.export.as_ref())
+            )
+        };
+
+        let before = TelemetryBuilder::new("svc")
+            .with_sampling_ratio(0.25)
+            .with_export_config(config.clone());
+        let after = TelemetryBuilder::new("svc")
+            .with_export_config(config.clone())
+            .with_sampling_ratio(0.25);
+        assert_eq!(sampler(before), "ParentBased(TraceIdRatioBased(0.25))");
+        assert_eq!(sampler(after), "ParentBased(TraceIdRatioBased(0.25))");
+
+        let configured = TelemetryBuilder::new("svc").with_export_config(config);
+        assert_eq!(sampler(configured), "ParentBased(TraceIdRatioBased(0.5))");
+        assert_eq!(
+            sampler(TelemetryBuilder::new("svc")),
+            "ParentBased(AlwaysOn)"
+        );
+    }
+
+    #[test]
+    fn grpc_metadata_carries_headers() {
+        let headers = HashMap::from([("DD_API_KEY".to_string(), "secret".to_string())]);
+        let metadata = grpc_metadata(&headers).expect("valid metadata");
+        assert_eq!(
+            metadata.get("dd_api_key").and_then(|v| v.to_str().ok()),
+            Some("secret")
+        );
+    }
+}
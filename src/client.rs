@@ -3,26 +3,18 @@ use once_cell::sync::{Lazy, OnceCell};
 use opentelemetry::{
     KeyValue, global,
     metrics::Histogram,
-    trace::{Span as _, SpanKind, TraceId, Tracer as _, TracerProvider},
-};
-use opentelemetry_otlp::{MetricExporter, SpanExporter, WithExportConfig};
-use opentelemetry_sdk::{
-    metrics::SdkMeterProvider,
-    propagation::TraceContextPropagator,
-    resource::Resource,
-    trace::{BatchSpanProcessor, SdkTracerProvider},
+    trace::{Span as _, SpanKind, TraceId, Tracer as _},
 };
 use serde_json::{Map, Value, json};
 use std::{collections::HashMap, sync::Mutex};
 use tracing::Level;
-use tracing_subscriber::{EnvFilter, fmt, prelude::*};
+
+use crate::builder::{FmtStyle, TelemetryBuilder};
 
 static CLIENT_STATE: OnceCell<ClientMode> = OnceCell::new();
 static TRACE_ID: Lazy<Mutex<Option<TraceId>>> = Lazy::new(|| Mutex::new(None));
 static HISTOGRAMS: Lazy<Mutex<HashMap<String, Histogram<f64>>>> =
     Lazy::new(|| Mutex::new(HashMap::new()));
-static CLIENT_TRACER_PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
-static CLIENT_METER_PROVIDER: OnceCell<SdkMeterProvider> = OnceCell::new();
 
 #[derive(Clone, Copy)]
 enum ClientMode {
@@ -39,70 +31,22 @@ pub fn init(otlp_endpoint: Option<&str>) -> Result<()> {
         return Ok(());
     }
 
-    let service_name =
-        std::env::var("SERVICE_NAME").unwrap_or_else(|_| "greentic-telemetry-client".into());
-    let resource = Resource::builder()
-        .with_service_name(service_name.clone())
-        .build();
-
-    let filter = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new("info"))
-        .unwrap();
-
-    // Install propagator once.
-    global::set_text_map_propagator(TraceContextPropagator::new());
-
-    let mode = if let Some(endpoint) = otlp_endpoint {
-        let mut span_exporter_builder = SpanExporter::builder().with_tonic();
-        span_exporter_builder = span_exporter_builder.with_endpoint(endpoint.to_string());
-        let span_exporter = span_exporter_builder.build()?;
-
-        let span_processor = BatchSpanProcessor::builder(span_exporter).build();
-        let tracer_provider = SdkTracerProvider::builder()
-            .with_resource(resource.clone())
-            .with_span_processor(span_processor)
-            .build();
-        let tracer = tracer_provider.tracer("greentic-telemetry-client");
-        global::set_tracer_provider(tracer_provider.clone());
-        let _ = CLIENT_TRACER_PROVIDER.set(tracer_provider);
-
-        let mut metric_exporter_builder = MetricExporter::builder().with_tonic();
-        metric_exporter_builder = metric_exporter_builder.with_endpoint(endpoint.to_string());
-        let metric_exporter = metric_exporter_builder.build()?;
-        let meter_provider = SdkMeterProvider::builder()
-            .with_resource(resource)
-            .with_periodic_exporter(metric_exporter)
-            .build();
-        global::set_meter_provider(meter_provider.clone());
-        let _ = CLIENT_METER_PROVIDER.set(meter_provider);
-
-        let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
-        let fmt_layer = fmt::layer()
-            .json()
-            .with_current_span(true)
-            .with_span_list(true)
-            .with_target(true);
-
-        let _ = tracing_subscriber::registry()
-            .with(filter)
-            .with(telemetry)
-            .with(fmt_layer)
-            .try_init();
-
+    let mode = if otlp_endpoint.is_some() {
         ClientMode::Otel
     } else {
-        let fmt_layer = fmt::layer()
-            .json()
-            .with_current_span(true)
-            .with_span_list(true)
-            .with_target(true);
-        let _ = tracing_subscriber::registry()
-            .with(filter)
-            .with(fmt_layer)
-            .try_init();
         ClientMode::JsonOnly
     };
 
+    if !crate::init::is_installed() {
+        let service_name =
+            std::env::var("SERVICE_NAME").unwrap_or_else(|_| "greentic-telemetry-client".into());
+        let mut builder = TelemetryBuilder::new(service_name).with_fmt(FmtStyle::Json);
+        if let Some(endpoint) = otlp_endpoint {
+            builder = builder.with_otlp_endpoint(endpoint);
+        }
+        builder.install()?.detach();
+    }
+
     let _ = CLIENT_STATE.set(mode);
     Ok(())
 }
@@ -1,11 +1,153 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Result, anyhow};
+use once_cell::sync::Lazy;
+use thiserror::Error;
+
+use crate::tasklocal::{
+    ContextGuard, overlay_current, set_task_or_thread_ctx, with_current_telemetry_ctx,
+};
+
+const DEFAULT_PREFIX: &str = "gt";
+const CORE_KEYS: [&str; 5] = [
+    "gt.tenant",
+    "gt.session",
+    "gt.flow",
+    "gt.node",
+    "gt.provider",
+];
+const CORE_NAMES: [&str; 5] = ["tenant", "session", "flow", "node", "provider"];
+
+static CONTEXT_KEYS: Lazy<RwLock<Arc<ContextKeys>>> = Lazy::new(|| {
+    let prefix = match std::env::var("TELEMETRY_KEY_PREFIX") {
+        Ok(prefix) => match validate_prefix(prefix.trim()) {
+            Ok(()) => prefix.trim().to_string(),
+            Err(err) => {
+                tracing::warn!("ignoring TELEMETRY_KEY_PREFIX: {err}");
+                DEFAULT_PREFIX.to_string()
+            }
+        },
+        Err(_) => DEFAULT_PREFIX.to_string(),
+    };
+    RwLock::new(Arc::new(ContextKeys::new(prefix)))
+});
+
+/// Set the process-wide key prefix, which new contexts use and which spans,
+/// baggage, metrics and samplers are read with. Defaults to
+/// `TELEMETRY_KEY_PREFIX`, else `gt`.
+///
+/// Call it before building policies and configs whose defaults name context
+/// keys, such as [`crate::BaggagePolicy`] or
+/// [`crate::metrics::MetricAttributePolicy`].
+pub fn set_key_prefix(prefix: impl Into<String>) -> Result<()> {
+    let prefix = prefix.into();
+    validate_prefix(&prefix)?;
+    *CONTEXT_KEYS.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(ContextKeys::new(prefix));
+    Ok(())
+}
+
+/// The process-wide key prefix; see [`set_key_prefix`].
+pub fn key_prefix() -> String {
+    context_keys().prefix.clone()
+}
+
+pub(crate) fn context_keys() -> Arc<ContextKeys> {
+    CONTEXT_KEYS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+fn validate_prefix(prefix: &str) -> Result<()> {
+    let valid = !prefix.is_empty()
+        && !prefix.starts_with('.')
+        && !prefix.ends_with('.')
+        && prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "invalid key prefix '{prefix}', expected letters, digits, '_', '-' or inner '.'"
+        ))
+    }
+}
+
+/// Full keys under the process-wide prefix.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+pub(crate) struct ContextKeys {
+    prefix: String,
+    /// `<prefix>.`, for matching.
+    namespace: String,
+    pub(crate) tenant: String,
+    pub(crate) flow: String,
+}
+
+impl ContextKeys {
+    fn new(prefix: String) -> Self {
+        Self {
+            namespace: format!("{prefix}."),
+            tenant: format!("{prefix}.tenant"),
+            flow: format!("{prefix}.flow"),
+            prefix,
+        }
+    }
+
+    /// `<prefix>.<name>`.
+    pub(crate) fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.namespace)
+    }
+
+    /// The name under the prefix, if `key` has it.
+    #[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+    pub(crate) fn name<'a>(&self, key: &'a str) -> Option<&'a str> {
+        key.strip_prefix(self.namespace.as_str())
+    }
+}
+
+/// Returned when a custom attribute would shadow a core field.
+#[derive(Debug, Error)]
+#[error("'{0}' names a core TelemetryCtx field and cannot be a custom attribute")]
+pub struct ReservedAttrError(pub String);
+
 /// Tenant-aware telemetry context propagated to spans and exporters.
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+///
+/// Besides the five core fields, a context carries custom attributes in
+/// insertion order. Every key is emitted as `<prefix>.<name>`, with the
+/// prefix defaulting to the process-wide one (see [`set_key_prefix`]).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TelemetryCtx {
     pub tenant: String,
     pub session: Option<String>,
     pub flow: Option<String>,
     pub node: Option<String>,
     pub provider: Option<String>,
+    prefix: Cow<'static, str>,
+    attributes: Vec<(String, AttrValue)>,
+}
+
+impl Default for TelemetryCtx {
+    fn default() -> Self {
+        let keys = context_keys();
+        let prefix = match keys.prefix.as_str() {
+            DEFAULT_PREFIX => Cow::Borrowed(DEFAULT_PREFIX),
+            prefix => Cow::Owned(prefix.to_string()),
+        };
+        Self {
+            tenant: String::new(),
+            session: None,
+            flow: None,
+            node: None,
+            provider: None,
+            prefix,
+            attributes: Vec::new(),
+        }
+    }
 }
 
 impl TelemetryCtx {
@@ -36,13 +178,486 @@ impl TelemetryCtx {
         self
     }
 
-    pub fn kv(&self) -> [(&'static str, Option<&str>); 5] {
-        [
-            ("gt.tenant", Some(self.tenant.as_str())),
-            ("gt.session", self.session.as_deref()),
-            ("gt.flow", self.flow.as_deref()),
-            ("gt.node", self.node.as_deref()),
-            ("gt.provider", self.provider.as_deref()),
-        ]
+    /// Namespace used for every emitted key, e.g. `acme` yields `acme.tenant`.
+    ///
+    /// Spans, baggage and metrics are read with the process-wide prefix, so
+    /// prefer [`set_key_prefix`] unless this context is only emitted.
+    pub fn with_prefix(mut self, prefix: impl Into<Cow<'static, str>>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Add or replace a custom attribute such as `team`, `user` or `run_id`.
+    ///
+    /// # Panics
+    ///
+    /// If `key` names a core field, e.g. `tenant`; use [`Self::set_attr`] to
+    /// handle that case.
+    pub fn with_attr(mut self, key: impl Into<String>, value: impl Into<AttrValue>) -> Self {
+        if let Err(err) = self.set_attr(key, value) {
+            panic!("{err}");
+        }
+        self
+    }
+
+    /// Add or replace a custom attribute, refusing the names of the core
+    /// fields.
+    pub fn set_attr(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<AttrValue>,
+    ) -> Result<(), ReservedAttrError> {
+        let key = key.into();
+        if CORE_NAMES.contains(&key.as_str()) {
+            return Err(ReservedAttrError(key));
+        }
+        self.insert_attr(key, value.into());
+        Ok(())
+    }
+
+    /// [`Self::set_attr`] for keys known not to name a core field.
+    pub(crate) fn insert_attr(&mut self, key: impl Into<String>, value: impl Into<AttrValue>) {
+        let key = key.into();
+        let value = value.into();
+        match self.attributes.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.attributes.push((key, value)),
+        }
+    }
+
+    pub fn remove_attr(&mut self, key: &str) -> Option<AttrValue> {
+        let index = self.attributes.iter().position(|(k, _)| k == key)?;
+        Some(self.attributes.remove(index).1)
+    }
+
+    pub fn attr(&self, key: &str) -> Option<&AttrValue> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Custom attributes in insertion order, without the prefix.
+    pub fn attributes(&self) -> impl Iterator<Item = (&str, &AttrValue)> {
+        self.attributes.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Value of a core field or custom attribute by its unprefixed name.
+    pub fn value(&self, name: &str) -> Option<CtxValue<'_>> {
+        let core = match name {
+            "tenant" => Some(self.tenant.as_str()).filter(|tenant| !tenant.is_empty()),
+            "session" => self.session.as_deref(),
+            "flow" => self.flow.as_deref(),
+            "node" => self.node.as_deref(),
+            "provider" => self.provider.as_deref(),
+            _ => return self.attr(name).map(AttrValue::as_value),
+        };
+        core.map(CtxValue::Str)
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Layer `child` over `self`: fields and attributes set on the child win,
+    /// everything else is inherited. The parent's prefix is kept.
+    pub fn overlay(&self, child: &TelemetryCtx) -> TelemetryCtx {
+        let mut merged = self.clone();
+        if !child.tenant.is_empty() {
+            merged.tenant.clone_from(&child.tenant);
+        }
+        for (target, source) in [
+            (&mut merged.session, &child.session),
+            (&mut merged.flow, &child.flow),
+            (&mut merged.node, &child.node),
+            (&mut merged.provider, &child.provider),
+        ] {
+            if source.is_some() {
+                target.clone_from(source);
+            }
+        }
+        for (key, value) in &child.attributes {
+            merged.insert_attr(key.clone(), value.clone());
+        }
+        merged
+    }
+
+    /// Layer `self` over the current context until the guard is dropped, e.g.
+    /// `TelemetryCtx::default().with_node("n2").enter()` changes only `node`.
+    ///
+    /// Inside [`crate::with_task_local`] the guard may be held across `.await`.
+    /// Elsewhere in a Tokio runtime it changes nothing, as the worker thread
+    /// is shared with other tasks; use [`crate::scope`] there instead.
+    pub fn enter(&self) -> ContextGuard {
+        ContextGuard::replace(Some(overlay_current(self)))
+    }
+
+    /// Prefixed key/value pairs for every field that is set: the core fields
+    /// first, then custom attributes in insertion order.
+    pub fn kv(&self) -> impl Iterator<Item = (Cow<'_, str>, CtxValue<'_>)> {
+        let core = [
+            Some(self.tenant.as_str()).filter(|tenant| !tenant.is_empty()),
+            self.session.as_deref(),
+            self.flow.as_deref(),
+            self.node.as_deref(),
+            self.provider.as_deref(),
+        ];
+        let core = CORE_KEYS
+            .into_iter()
+            .zip(core)
+            .filter_map(move |(key, value)| Some((self.core_key(key), CtxValue::Str(value?))));
+        let custom = self
+            .attributes
+            .iter()
+            .map(|(key, value)| (Cow::Owned(self.key(key)), value.as_value()));
+        core.chain(custom)
+    }
+
+    fn core_key(&self, key: &'static str) -> Cow<'_, str> {
+        if self.prefix == DEFAULT_PREFIX {
+            Cow::Borrowed(key)
+        } else {
+            Cow::Owned(self.key(&key[DEFAULT_PREFIX.len() + 1..]))
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{name}", self.prefix)
+        }
+    }
+}
+
+/// Legacy cloud-propagation shape (`x-tenant`, `x-team`, `x-flow`, `x-run-id`).
+///
+/// Converts into [`TelemetryCtx`] with `team` and `run_id` as custom attributes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CloudCtx<'a> {
+    pub tenant: Option<&'a str>,
+    pub team: Option<&'a str>,
+    pub flow: Option<&'a str>,
+    pub run_id: Option<&'a str>,
+}
+
+impl CloudCtx<'_> {
+    pub const fn empty() -> Self {
+        Self {
+            tenant: None,
+            team: None,
+            flow: None,
+            run_id: None,
+        }
+    }
+}
+
+impl From<CloudCtx<'_>> for TelemetryCtx {
+    fn from(cloud: CloudCtx<'_>) -> Self {
+        let mut ctx = TelemetryCtx::new(cloud.tenant.unwrap_or_default());
+        ctx.flow = cloud.flow.map(str::to_string);
+        if let Some(team) = cloud.team {
+            ctx.insert_attr("team", team);
+        }
+        if let Some(run_id) = cloud.run_id {
+            ctx.insert_attr("run_id", run_id);
+        }
+        ctx
+    }
+}
+
+/// Replace the current context: the task-local one inside
+/// [`crate::with_task_local`], or the thread-local one in synchronous code
+/// outside any Tokio runtime, as [`crate::set_current_telemetry_ctx`] does.
+///
+/// An empty context (no tenant and nothing else set) clears it.
+pub fn set_context(ctx: impl Into<TelemetryCtx>) {
+    let ctx = ctx.into();
+    let empty = ctx.kv().next().is_none();
+    set_task_or_thread_ctx((!empty).then_some(ctx));
+}
+
+/// Clone of the context that spans, logs, metrics and propagation would use now.
+pub fn context_snapshot() -> Option<TelemetryCtx> {
+    with_current_telemetry_ctx(|ctx| ctx.cloned())
+}
+
+/// Owned value of a custom context attribute.
+///
+/// Floats compare and hash by bit pattern so that contexts stay `Eq` and
+/// `Hash`; `NaN` equals itself and `0.0` differs from `-0.0`.
+#[derive(Clone, Debug)]
+pub enum AttrValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Float(f64),
+}
+
+impl AttrValue {
+    pub fn as_value(&self) -> CtxValue<'_> {
+        match self {
+            AttrValue::Str(v) => CtxValue::Str(v),
+            AttrValue::Int(v) => CtxValue::Int(*v),
+            AttrValue::Bool(v) => CtxValue::Bool(*v),
+            AttrValue::Float(v) => CtxValue::Float(*v),
+        }
+    }
+}
+
+impl PartialEq for AttrValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (AttrValue::Str(a), AttrValue::Str(b)) => a == b,
+            (AttrValue::Int(a), AttrValue::Int(b)) => a == b,
+            (AttrValue::Bool(a), AttrValue::Bool(b)) => a == b,
+            (AttrValue::Float(a), AttrValue::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for AttrValue {}
+
+impl Hash for AttrValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            AttrValue::Str(v) => v.hash(state),
+            AttrValue::Int(v) => v.hash(state),
+            AttrValue::Bool(v) => v.hash(state),
+            AttrValue::Float(v) => v.to_bits().hash(state),
+        }
+    }
+}
+
+impl From<String> for AttrValue {
+    fn from(v: String) -> Self {
+        AttrValue::Str(v)
+    }
+}
+
+impl From<&str> for AttrValue {
+    fn from(v: &str) -> Self {
+        AttrValue::Str(v.to_string())
+    }
+}
+
+impl From<i64> for AttrValue {
+    fn from(v: i64) -> Self {
+        AttrValue::Int(v)
+    }
+}
+
+impl From<i32> for AttrValue {
+    fn from(v: i32) -> Self {
+        AttrValue::Int(v.into())
+    }
+}
+
+impl From<bool> for AttrValue {
+    fn from(v: bool) -> Self {
+        AttrValue::Bool(v)
+    }
+}
+
+impl From<f64> for AttrValue {
+    fn from(v: f64) -> Self {
+        AttrValue::Float(v)
+    }
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_value().fmt(f)
+    }
+}
+
+/// Borrowed view of a context value as yielded by [`TelemetryCtx::kv`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CtxValue<'a> {
+    Str(&'a str),
+    Int(i64),
+    Bool(bool),
+    Float(f64),
+}
+
+impl CtxValue<'_> {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            CtxValue::Str(v) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CtxValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CtxValue::Str(v) => f.write_str(v),
+            CtxValue::Int(v) => v.fmt(f),
+            CtxValue::Bool(v) => v.fmt(f),
+            CtxValue::Float(v) => v.fmt(f),
+        }
+    }
+}
+
+impl From<CtxValue<'_>> for serde_json::Value {
+    fn from(value: CtxValue<'_>) -> Self {
+        match value {
+            CtxValue::Str(v) => v.into(),
+            CtxValue::Int(v) => v.into(),
+            CtxValue::Bool(v) => v.into(),
+            CtxValue::Float(v) => v.into(),
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl From<CtxValue<'_>> for opentelemetry::Value {
+    fn from(value: CtxValue<'_>) -> Self {
+        match value {
+            CtxValue::Str(v) => v.to_string().into(),
+            CtxValue::Int(v) => v.into(),
+            CtxValue::Bool(v) => v.into(),
+            CtxValue::Float(v) => v.into(),
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl From<CtxValue<'_>> for opentelemetry::logs::AnyValue {
+    fn from(value: CtxValue<'_>) -> Self {
+        match value {
+            CtxValue::Str(v) => v.to_string().into(),
+            CtxValue::Int(v) => v.into(),
+            CtxValue::Bool(v) => v.into(),
+            CtxValue::Float(v) => v.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cloud_ctx_converts_without_loss() {
+        let ctx = TelemetryCtx::from(CloudCtx {
+            tenant: Some("acme"),
+            team: Some("payments"),
+            flow: Some("intake"),
+            run_id: Some("run-1"),
+        });
+        assert_eq!(
+            keys(&ctx),
+            [
+                ("gt.tenant", "acme"),
+                ("gt.flow", "intake"),
+                ("gt.team", "payments"),
+                ("gt.run_id", "run-1"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+
+    #[test]
+    fn contexts_with_float_attributes_work_as_map_keys() {
+        use std::collections::HashSet;
+
+        let ctx = TelemetryCtx::new("acme").with_attr("ratio", f64::NAN);
+        assert_eq!(ctx, ctx.clone());
+        assert_ne!(
+            TelemetryCtx::new("acme").with_attr("ratio", 0.0),
+            TelemetryCtx::new("acme").with_attr("ratio", -0.0)
+        );
+
+        let set: HashSet<TelemetryCtx> = [ctx.clone(), ctx].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+
+    fn keys(ctx: &TelemetryCtx) -> Vec<(String, String)> {
+        ctx.kv()
+            .map(|(k, v)| (k.into_owned(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn kv_emits_set_fields_then_attributes_in_order() {
+        let ctx = TelemetryCtx::new("acme")
+            .with_flow("intake")
+            .with_attr("team", "core")
+            .with_attr("run_id", 42)
+            .with_attr("dry_run", true)
+            .with_attr("team", "platform");
+
+        assert_eq!(
+            keys(&ctx),
+            [
+                ("gt.tenant", "acme"),
+                ("gt.flow", "intake"),
+                ("gt.team", "platform"),
+                ("gt.run_id", "42"),
+                ("gt.dry_run", "true"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+        assert_eq!(ctx.attr("run_id"), Some(&AttrValue::Int(42)));
+    }
+
+    #[test]
+    fn custom_attributes_cannot_shadow_core_fields() {
+        let mut ctx = TelemetryCtx::new("acme");
+        assert!(ctx.set_attr("tenant", "forged").is_err());
+        assert!(ctx.set_attr("tenant_id", "t-1").is_ok());
+        assert_eq!(ctx.tenant, "acme");
+        assert_eq!(ctx.attr("tenant"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "core TelemetryCtx field")]
+    fn with_attr_panics_on_core_names() {
+        let _ = TelemetryCtx::new("acme").with_attr("flow", "intake");
+    }
+
+    #[test]
+    fn prefix_applies_to_every_key() {
+        let ctx = TelemetryCtx::new("acme")
+            .with_prefix("acme")
+            .with_attr("user", "u1");
+        let keys: Vec<_> = keys(&ctx).into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, ["acme.tenant", "acme.user"]);
+    }
+
+    #[test]
+    fn overlay_prefers_child_values() {
+        let parent = TelemetryCtx::new("acme")
+            .with_session("s1")
+            .with_flow("intake")
+            .with_attr("team", "core")
+            .with_attr("user", "u1");
+        let child = TelemetryCtx::default()
+            .with_node("parse")
+            .with_flow("retry")
+            .with_attr("user", "u2")
+            .with_attr("tool", "search");
+
+        let merged = parent.overlay(&child);
+        assert_eq!(merged.tenant, "acme");
+        assert_eq!(merged.session.as_deref(), Some("s1"));
+        assert_eq!(merged.flow.as_deref(), Some("retry"));
+        assert_eq!(merged.node.as_deref(), Some("parse"));
+        let attrs: Vec<_> = merged
+            .attributes()
+            .map(|(k, v)| (k, v.to_string()))
+            .collect();
+        assert_eq!(
+            attrs,
+            [
+                ("team", "core".to_string()),
+                ("user", "u2".to_string()),
+                ("tool", "search".to_string())
+            ]
+        );
     }
 }
@@ -4,6 +4,8 @@ use std::env;
 use anyhow::{Context, Result, anyhow};
 
 use crate::presets::{self, CloudPreset, PresetConfig};
+use crate::sampling::{TailSamplingConfig, TenantRateConfig};
+use crate::span_metrics::SpanMetricsConfig;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ExportMode {
@@ -12,25 +14,24 @@ pub enum ExportMode {
     OtlpHttp,
 }
 
-#[derive(Clone, Copy, Debug)]
-#[cfg_attr(
-    not(any(feature = "otlp-grpc", feature = "otlp-http")),
-    allow(dead_code)
-)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Sampling {
     Parent,
     TraceIdRatio(f64),
 }
 
-#[cfg_attr(
-    not(any(feature = "otlp-grpc", feature = "otlp-http")),
-    allow(dead_code)
-)]
+#[derive(Clone, Debug)]
 pub struct ExportConfig {
     pub mode: ExportMode,
     pub endpoint: Option<String>,
     pub headers: HashMap<String, String>,
     pub sampling: Sampling,
+    pub tail_sampling: Option<TailSamplingConfig>,
+    pub tenant_rate: Option<TenantRateConfig>,
+    pub span_metrics: Option<SpanMetricsConfig>,
+    /// Also serve metrics for Prometheus to scrape on this address, next to
+    /// the exporter picked by `mode`.
+    pub prometheus: Option<String>,
 }
 
 impl ExportConfig {
@@ -40,9 +41,37 @@ impl ExportConfig {
             endpoint: None,
             headers: HashMap::new(),
             sampling: Sampling::Parent,
+            tail_sampling: None,
+            tenant_rate: None,
+            span_metrics: None,
+            prometheus: None,
         }
     }
 
+    /// OTLP gRPC export to `endpoint` with no extra headers.
+    pub fn otlp_grpc(endpoint: impl Into<String>) -> Self {
+        Self {
+            mode: ExportMode::OtlpGrpc,
+            endpoint: Some(endpoint.into()),
+            ..Self::json_default()
+        }
+    }
+
+    /// OTLP/HTTP (protobuf) export to the collector base URL `endpoint`.
+    pub fn otlp_http(endpoint: impl Into<String>) -> Self {
+        Self {
+            mode: ExportMode::OtlpHttp,
+            endpoint: Some(endpoint.into()),
+            ..Self::json_default()
+        }
+    }
+
+    /// Also serve metrics for Prometheus on `addr`, e.g. `0.0.0.0:9464`.
+    pub fn with_prometheus(mut self, addr: impl Into<String>) -> Self {
+        self.prometheus = Some(addr.into());
+        self
+    }
+
     pub fn from_env() -> Result<Self> {
         let preset = presets::detect_from_env().and_then(|preset| match preset {
             CloudPreset::None => None,
@@ -76,6 +105,18 @@ impl ExportConfig {
         if endpoint.is_none() {
             endpoint = preset_config.otlp_endpoint;
         }
+        let prometheus = match env::var("TELEMETRY_METRICS_EXPORT") {
+            Ok(value) if value.trim().eq_ignore_ascii_case("prometheus") => {
+                Some(prometheus_addr_from_env()?)
+            }
+            Ok(value) if value.trim().is_empty() => None,
+            Ok(other) => {
+                return Err(anyhow!(
+                    "unsupported TELEMETRY_METRICS_EXPORT value: {other}. expected prometheus"
+                ));
+            }
+            Err(_) => None,
+        };
 
         let mut headers = parse_headers(env::var("OTLP_HEADERS").ok().as_deref())?;
         if headers.is_empty() {
@@ -83,6 +124,18 @@ impl ExportConfig {
         }
 
         let sampling = parse_sampling(env::var("TELEMETRY_SAMPLING").ok().as_deref())?;
+        let tail_sampling = match env::var("TELEMETRY_TAIL_SAMPLING") {
+            Ok(value) => TailSamplingConfig::parse(&value)?,
+            Err(_) => None,
+        };
+        let tenant_rate = match env::var("TELEMETRY_TENANT_RATE") {
+            Ok(value) => TenantRateConfig::parse(&value)?,
+            Err(_) => None,
+        };
+        let span_metrics = match env::var("TELEMETRY_SPAN_METRICS") {
+            Ok(value) => SpanMetricsConfig::parse(&value)?,
+            Err(_) => None,
+        };
 
         let inferred_mode = if explicit_export.is_none() {
             preset_config.export_mode.unwrap_or(match preset {
@@ -98,6 +151,10 @@ impl ExportConfig {
             endpoint,
             headers,
             sampling,
+            tail_sampling,
+            tenant_rate,
+            span_metrics,
+            prometheus,
         })
     }
 }
@@ -137,6 +194,24 @@ fn parse_headers(value: Option<&str>) -> Result<HashMap<String, String>> {
     Ok(headers)
 }
 
+/// `OTEL_EXPORTER_PROMETHEUS_HOST` (default `0.0.0.0`, so scrapers on
+/// other hosts reach it) and
+/// `OTEL_EXPORTER_PROMETHEUS_PORT` (default `9464`).
+fn prometheus_addr_from_env() -> Result<String> {
+    let host = env::var("OTEL_EXPORTER_PROMETHEUS_HOST")
+        .ok()
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "0.0.0.0".to_string());
+    let port = match env::var("OTEL_EXPORTER_PROMETHEUS_PORT") {
+        Ok(port) => port
+            .trim()
+            .parse::<u16>()
+            .with_context(|| format!("invalid OTEL_EXPORTER_PROMETHEUS_PORT: {port}"))?,
+        Err(_) => 9464,
+    };
+    Ok(format!("{host}:{port}"))
+}
+
 fn parse_sampling(value: Option<&str>) -> Result<Sampling> {
     let Some(value) = value else {
         return Ok(Sampling::Parent);
@@ -164,7 +239,7 @@ fn parse_sampling(value: Option<&str>) -> Result<Sampling> {
     ))
 }
 
-#[cfg(any(feature = "otlp-grpc", feature = "otlp-http"))]
+#[cfg(feature = "otlp")]
 impl Sampling {
     pub(crate) fn into_sampler(self) -> opentelemetry_sdk::trace::Sampler {
         use opentelemetry_sdk::trace::Sampler;
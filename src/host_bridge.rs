@@ -1,4 +1,5 @@
 use crate::client;
+use crate::context::TelemetryCtx;
 use anyhow::{Context, Result};
 use serde::Deserialize;
 use serde_json::Value;
@@ -17,6 +18,34 @@ pub struct HostContext {
     pub action: Option<String>,
 }
 
+/// Maps `flow_id`/`node_id` onto the core fields and the remaining host
+/// fields onto custom attributes of the same name.
+impl From<&HostContext> for TelemetryCtx {
+    fn from(host: &HostContext) -> Self {
+        let mut ctx = TelemetryCtx::new(host.tenant.as_str());
+        ctx.flow = Some(host.flow_id.clone()).filter(|flow| !flow.is_empty());
+        ctx.node = host.node_id.clone();
+        for (key, value) in [
+            ("team", &host.team),
+            ("user", &host.user),
+            ("connector", &host.connector),
+            ("tool", &host.tool),
+            ("action", &host.action),
+        ] {
+            if let Some(value) = value {
+                ctx.insert_attr(key, value.as_str());
+            }
+        }
+        ctx
+    }
+}
+
+impl From<HostContext> for TelemetryCtx {
+    fn from(host: HostContext) -> Self {
+        TelemetryCtx::from(&host)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct HostSpan<'a> {
     #[serde(default)]
@@ -39,26 +68,10 @@ pub fn emit_span(span_json: &str, ctx: &HostContext) -> Result<()> {
         owned.push(((*key).to_string(), val));
     }
 
-    // Standard labels
-    owned.push(("tenant".into(), ctx.tenant.clone()));
-    owned.push(("flow_id".into(), ctx.flow_id.clone()));
-    if let Some(team) = &ctx.team {
-        owned.push(("team".into(), team.clone()));
-    }
-    if let Some(user) = &ctx.user {
-        owned.push(("user".into(), user.clone()));
-    }
-    if let Some(node) = &ctx.node_id {
-        owned.push(("node_id".into(), node.clone()));
-    }
-    if let Some(connector) = &ctx.connector {
-        owned.push(("connector".into(), connector.clone()));
-    }
-    if let Some(tool) = &ctx.tool {
-        owned.push(("tool".into(), tool.clone()));
-    }
-    if let Some(action) = &ctx.action {
-        owned.push(("action".into(), action.clone()));
+    // Standard labels, named as on every other span.
+    let ctx = TelemetryCtx::from(ctx);
+    for (key, value) in ctx.kv() {
+        owned.push((key.into_owned(), value.to_string()));
     }
 
     let owned_refs: Vec<(&str, &str)> = owned
@@ -75,3 +88,40 @@ pub fn emit_span(span_json: &str, ctx: &HostContext) -> Result<()> {
     client::span(name, &owned_refs);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_context_maps_onto_telemetry_ctx() {
+        let host = HostContext {
+            tenant: "acme".into(),
+            team: Some("payments".into()),
+            user: Some("u1".into()),
+            flow_id: "intake".into(),
+            node_id: Some("parse".into()),
+            connector: None,
+            tool: Some("search".into()),
+            action: Some("query".into()),
+        };
+
+        let keys: Vec<(String, String)> = TelemetryCtx::from(&host)
+            .kv()
+            .map(|(k, v)| (k.into_owned(), v.to_string()))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("gt.tenant", "acme"),
+                ("gt.flow", "intake"),
+                ("gt.node", "parse"),
+                ("gt.team", "payments"),
+                ("gt.user", "u1"),
+                ("gt.tool", "search"),
+                ("gt.action", "query"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+}
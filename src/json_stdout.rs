@@ -0,0 +1,237 @@
+//! Exporters that write spans, metrics and logs to stdout as one JSON object per line.
+
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use opentelemetry::{KeyValue, Value as OtelValue, logs::AnyValue, trace::Status};
+use opentelemetry_sdk::{
+    Resource,
+    error::OTelSdkResult,
+    logs::{LogBatch, LogExporter},
+    metrics::{
+        Temporality,
+        data::{AggregatedMetrics, MetricData, ResourceMetrics},
+        exporter::PushMetricExporter,
+    },
+    trace::{SpanData, SpanExporter},
+};
+use serde_json::{Map, Value, json};
+
+#[derive(Debug, Default)]
+pub(crate) struct JsonStdoutSpanExporter {
+    resource: Map<String, Value>,
+}
+
+impl SpanExporter for JsonStdoutSpanExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut stdout = std::io::stdout().lock();
+        for span in batch {
+            let line = json!({
+                "resource": self.resource,
+                "scope": span.instrumentation_scope.name(),
+                "name": span.name,
+                "kind": format!("{:?}", span.span_kind),
+                "trace_id": span.span_context.trace_id().to_string(),
+                "span_id": span.span_context.span_id().to_string(),
+                "parent_span_id": span.parent_span_id.to_string(),
+                "start_unix_nano": unix_nanos(span.start_time),
+                "end_unix_nano": unix_nanos(span.end_time),
+                "status": status_json(&span.status),
+                "attributes": attributes_json(span.attributes.iter()),
+                "events": span
+                    .events
+                    .iter()
+                    .map(|event| json!({
+                        "name": event.name,
+                        "time_unix_nano": unix_nanos(event.timestamp),
+                        "attributes": attributes_json(event.attributes.iter()),
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+            let _ = writeln!(stdout, "{line}");
+        }
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource_json(resource);
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct JsonStdoutMetricExporter;
+
+impl PushMetricExporter for JsonStdoutMetricExporter {
+    async fn export(&self, metrics: &ResourceMetrics) -> OTelSdkResult {
+        let resource = resource_json(metrics.resource());
+        let mut stdout = std::io::stdout().lock();
+        for scope in metrics.scope_metrics() {
+            for metric in scope.metrics() {
+                let points = match metric.data() {
+                    AggregatedMetrics::F64(data) => data_points_json(data),
+                    AggregatedMetrics::U64(data) => data_points_json(data),
+                    AggregatedMetrics::I64(data) => data_points_json(data),
+                };
+                let line = json!({
+                    "resource": resource,
+                    "scope": scope.scope().name(),
+                    "metric": metric.name(),
+                    "description": metric.description(),
+                    "unit": metric.unit(),
+                    "data_points": points,
+                });
+                let _ = writeln!(stdout, "{line}");
+            }
+        }
+        Ok(())
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        let _ = std::io::stdout().flush();
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn temporality(&self) -> Temporality {
+        Temporality::Cumulative
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct JsonStdoutLogExporter {
+    resource: Map<String, Value>,
+}
+
+impl LogExporter for JsonStdoutLogExporter {
+    async fn export(&self, batch: LogBatch<'_>) -> OTelSdkResult {
+        let mut stdout = std::io::stdout().lock();
+        for (record, scope) in batch.iter() {
+            let trace = record.trace_context();
+            let line = json!({
+                "resource": self.resource,
+                "scope": scope.name(),
+                "time_unix_nano": record.timestamp().or(record.observed_timestamp()).map(unix_nanos),
+                "severity": record.severity_text(),
+                "target": record.target().map(|t| t.to_string()),
+                "trace_id": trace.map(|t| t.trace_id.to_string()),
+                "span_id": trace.map(|t| t.span_id.to_string()),
+                "body": record.body().map(any_value_json),
+                "attributes": record
+                    .attributes_iter()
+                    .map(|(key, value)| (key.to_string(), any_value_json(value)))
+                    .collect::<Map<_, _>>(),
+            });
+            let _ = writeln!(stdout, "{line}");
+        }
+        Ok(())
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource_json(resource);
+    }
+}
+
+fn data_points_json<T>(data: &MetricData<T>) -> Vec<Value>
+where
+    T: Copy + Into<Value>,
+{
+    match data {
+        MetricData::Gauge(gauge) => gauge
+            .data_points()
+            .map(|point| {
+                json!({
+                    "attributes": attributes_json(point.attributes()),
+                    "value": point.value().into(),
+                })
+            })
+            .collect(),
+        MetricData::Sum(sum) => sum
+            .data_points()
+            .map(|point| {
+                json!({
+                    "attributes": attributes_json(point.attributes()),
+                    "value": point.value().into(),
+                })
+            })
+            .collect(),
+        MetricData::Histogram(histogram) => histogram
+            .data_points()
+            .map(|point| {
+                json!({
+                    "attributes": attributes_json(point.attributes()),
+                    "count": point.count(),
+                    "sum": point.sum().into(),
+                    "bounds": point.bounds().collect::<Vec<_>>(),
+                    "bucket_counts": point.bucket_counts().collect::<Vec<_>>(),
+                })
+            })
+            .collect(),
+        MetricData::ExponentialHistogram(histogram) => histogram
+            .data_points()
+            .map(|point| {
+                json!({
+                    "attributes": attributes_json(point.attributes()),
+                    "count": point.count(),
+                    "sum": point.sum().into(),
+                    "scale": point.scale(),
+                    "zero_count": point.zero_count(),
+                })
+            })
+            .collect(),
+    }
+}
+
+fn resource_json(resource: &Resource) -> Map<String, Value> {
+    resource
+        .iter()
+        .map(|(key, value)| (key.to_string(), value_json(value)))
+        .collect()
+}
+
+fn attributes_json<'a>(attributes: impl Iterator<Item = &'a KeyValue>) -> Map<String, Value> {
+    attributes
+        .map(|kv| (kv.key.to_string(), value_json(&kv.value)))
+        .collect()
+}
+
+fn value_json(value: &OtelValue) -> Value {
+    match value {
+        OtelValue::Bool(v) => Value::Bool(*v),
+        OtelValue::I64(v) => json!(v),
+        OtelValue::F64(v) => json!(v),
+        other => Value::String(other.to_string()),
+    }
+}
+
+fn any_value_json(value: &AnyValue) -> Value {
+    match value {
+        AnyValue::Int(v) => json!(v),
+        AnyValue::Double(v) => json!(v),
+        AnyValue::String(v) => Value::String(v.to_string()),
+        AnyValue::Boolean(v) => Value::Bool(*v),
+        AnyValue::ListAny(values) => Value::Array(values.iter().map(any_value_json).collect()),
+        AnyValue::Map(map) => map
+            .iter()
+            .map(|(key, value)| (key.to_string(), any_value_json(value)))
+            .collect::<Map<_, _>>()
+            .into(),
+        other => Value::String(format!("{other:?}")),
+    }
+}
+
+fn status_json(status: &Status) -> Value {
+    match status {
+        Status::Unset => json!({ "code": "unset" }),
+        Status::Ok => json!({ "code": "ok" }),
+        Status::Error { description } => json!({ "code": "error", "message": description }),
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
@@ -1,19 +1,32 @@
-use crate::context::TelemetryCtx;
+use crate::context::{CtxValue, TelemetryCtx};
 use crate::tasklocal::with_current_telemetry_ctx;
+#[cfg(feature = "otlp")]
+use opentelemetry::{
+    KeyValue,
+    trace::{SpanBuilder, Tracer},
+};
+use serde_json::{Map, Value};
+#[cfg(feature = "otlp")]
+use std::borrow::Cow;
 use std::sync::Arc;
 use tracing::Subscriber;
+#[cfg(feature = "otlp")]
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 use tracing_subscriber::{
+    fmt::{FormattedFields, format::JsonFields},
     layer::{Context, Layer},
     registry::LookupSpan,
 };
 
+type CtxProvider = Arc<dyn Fn() -> Option<TelemetryCtx> + Send + Sync>;
+
 #[derive(Clone)]
 struct ContextLayer {
-    provider: Arc<dyn Fn() -> Option<TelemetryCtx> + Send + Sync>,
+    provider: CtxProvider,
 }
 
 impl ContextLayer {
-    fn new(provider: Arc<dyn Fn() -> Option<TelemetryCtx> + Send + Sync>) -> Self {
+    fn new(provider: CtxProvider) -> Self {
         Self { provider }
     }
 }
@@ -35,27 +48,153 @@ where
         }
     }
 
+    /// Refreshes the context on first entry: declared fields are recorded for
+    /// every layer and JSON fmt fields are filled in.
     fn on_enter(&self, id: &tracing::span::Id, ctx: Context<'_, S>) {
-        if let Some(span) = ctx.span(id) {
-            let telemetry = span
-                .extensions()
-                .get::<TelemetryCtx>()
-                .cloned()
-                .or_else(|| (self.provider)());
-
-            if let Some(tctx) = telemetry {
-                let current = tracing::Span::current();
-                for (key, value) in tctx.kv() {
-                    if let Some(v) = value {
-                        current.record(key, tracing::field::display(v));
-                    }
-                }
-                let _ = span.extensions_mut().replace(tctx);
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if span.extensions().get::<Injected>().is_some() {
+            return;
+        }
+
+        let telemetry = span
+            .extensions()
+            .get::<TelemetryCtx>()
+            .cloned()
+            .or_else(|| (self.provider)());
+        let Some(tctx) = telemetry else {
+            return;
+        };
+
+        // The registry pushes the span before layers see `on_enter`, so the
+        // current span is the one being entered; bail out if it is not.
+        let current = tracing::Span::current();
+        let is_current = current.id().as_ref() == Some(id);
+        let fields = span.metadata().fields();
+
+        {
+            let mut extensions = span.extensions_mut();
+            if let Some(formatted) = extensions.get_mut::<FormattedFields<JsonFields>>() {
+                inject_json_fields(&mut formatted.fields, &tctx, |key| {
+                    is_current && fields.field(key).is_some()
+                });
+            }
+            extensions.replace(tctx.clone());
+            extensions.insert(Injected);
+        }
+
+        if !is_current {
+            return;
+        }
+        for (key, value) in tctx.kv() {
+            if fields.field(key.as_ref()).is_some() {
+                // Declared fields flow through every layer's `on_record`.
+                record_value(&current, &key, value);
+            } else {
+                #[cfg(feature = "otlp")]
+                current.set_attribute(key.into_owned(), value);
             }
         }
     }
 }
 
+fn record_value(span: &tracing::Span, key: &str, value: CtxValue<'_>) {
+    match value {
+        CtxValue::Str(v) => span.record(key, tracing::field::display(v)),
+        CtxValue::Int(v) => span.record(key, v),
+        CtxValue::Bool(v) => span.record(key, v),
+        CtxValue::Float(v) => span.record(key, v),
+    };
+}
+
+/// Marks spans whose context has already been attached.
+struct Injected;
+
+/// Add the context to a JSON fmt layer's span fields, skipping keys for which
+/// `declared` holds since those are recorded through the regular field path.
+fn inject_json_fields(fields: &mut String, tctx: &TelemetryCtx, declared: impl Fn(&str) -> bool) {
+    let mut map = match serde_json::from_str::<Map<String, Value>>(fields) {
+        Ok(map) => map,
+        Err(_) if fields.is_empty() => Map::new(),
+        Err(_) => return,
+    };
+    for (key, value) in tctx.kv() {
+        if !declared(&key) {
+            map.insert(key.into_owned(), value.into());
+        }
+    }
+    if let Ok(json) = serde_json::to_string(&map) {
+        *fields = json;
+    }
+}
+
+/// OTel tracer that stamps the context onto each span builder.
+///
+/// `tracing_opentelemetry` asks the tracer for a builder from its
+/// `on_new_span`, so spans carry the context from creation even when they are
+/// never entered. Keys recorded again later (declared fields, `on_enter`
+/// refreshes) replace the stamped value when the span is started.
+#[cfg(feature = "otlp")]
+#[derive(Clone)]
+pub(crate) struct ContextTracer<T> {
+    inner: T,
+    provider: CtxProvider,
+}
+
+#[cfg(feature = "otlp")]
+impl<T: Tracer> Tracer for ContextTracer<T> {
+    type Span = T::Span;
+
+    fn span_builder<N>(&self, name: N) -> SpanBuilder
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        let mut builder = self.inner.span_builder(name);
+        if let Some(tctx) = (self.provider)() {
+            builder.attributes.get_or_insert_with(Vec::new).extend(
+                tctx.kv()
+                    .map(|(key, value)| KeyValue::new(key.into_owned(), value)),
+            );
+        }
+        builder
+    }
+
+    fn build_with_context(
+        &self,
+        mut builder: SpanBuilder,
+        parent_cx: &opentelemetry::Context,
+    ) -> Self::Span {
+        if let Some(attributes) = builder.attributes.as_mut() {
+            dedup_keep_last(attributes);
+        }
+        self.inner.build_with_context(builder, parent_cx)
+    }
+}
+
+/// Drop earlier occurrences of repeated keys, matching OTel's "last write
+/// wins" attribute semantics.
+#[cfg(feature = "otlp")]
+fn dedup_keep_last(attributes: &mut Vec<KeyValue>) {
+    let mut seen = std::collections::HashSet::new();
+    let mut keep: Vec<bool> = attributes
+        .iter()
+        .rev()
+        .map(|kv| seen.insert(kv.key.clone()))
+        .collect();
+    keep.reverse();
+    let mut keep = keep.into_iter();
+    attributes.retain(|_| keep.next().unwrap_or(true));
+}
+
+#[cfg(feature = "otlp")]
+pub(crate) fn tracer_from_task_local<T: Tracer>(inner: T) -> ContextTracer<T> {
+    ContextTracer {
+        inner,
+        provider: Arc::new(|| with_current_telemetry_ctx(|ctx| ctx.cloned())),
+    }
+}
+
 pub fn layer_from_task_local() -> impl Layer<tracing_subscriber::Registry> + Clone {
     let provider = Arc::new(|| with_current_telemetry_ctx(|ctx| ctx.cloned()));
     ContextLayer::new(provider)
@@ -66,3 +205,165 @@ pub fn layer_with_provider(
 ) -> impl Layer<tracing_subscriber::Registry> + Clone {
     ContextLayer::new(Arc::new(provider))
 }
+
+#[cfg(all(test, feature = "otlp"))]
+mod tests {
+    use super::*;
+    use crate::builder::BoxedLayer;
+    use opentelemetry::{KeyValue, trace::TracerProvider as _};
+    use opentelemetry_sdk::{
+        error::OTelSdkResult,
+        trace::{SdkTracerProvider, SpanData, SpanProcessor},
+    };
+    use std::io::Write;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    #[derive(Debug, Default, Clone)]
+    struct CaptureProcessor {
+        spans: Arc<Mutex<Vec<SpanData>>>,
+    }
+
+    impl SpanProcessor for CaptureProcessor {
+        fn on_start(
+            &self,
+            _span: &mut opentelemetry_sdk::trace::Span,
+            _cx: &opentelemetry::Context,
+        ) {
+        }
+
+        fn on_end(&self, span: SpanData) {
+            self.spans.lock().expect("spans lock").push(span);
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().expect("buffer lock").extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn acme() -> Option<TelemetryCtx> {
+        Some(
+            TelemetryCtx::new("acme")
+                .with_flow("intake")
+                .with_node("n1")
+                .with_attr("run_id", 7),
+        )
+    }
+
+    #[test]
+    fn undeclared_fields_reach_otel_span() {
+        let capture = CaptureProcessor::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(capture.clone())
+            .build();
+
+        let layers: Vec<BoxedLayer> = vec![
+            layer_with_provider(acme).boxed(),
+            tracing_opentelemetry::layer()
+                .with_tracer(provider.tracer("layer-test"))
+                .boxed(),
+        ];
+        tracing::subscriber::with_default(Registry::default().with(layers), || {
+            let span = tracing::info_span!("plain", "gt.flow" = tracing::field::Empty);
+            let _entered = span.enter();
+        });
+
+        let spans = capture.spans.lock().expect("spans lock");
+        let attributes = &spans[0].attributes;
+        let get = |key: &str| {
+            attributes
+                .iter()
+                .filter(|kv| kv.key.as_str() == key)
+                .collect::<Vec<&KeyValue>>()
+        };
+        assert_eq!(get("gt.tenant"), [&KeyValue::new("gt.tenant", "acme")]);
+        assert_eq!(get("gt.node"), [&KeyValue::new("gt.node", "n1")]);
+        assert_eq!(get("gt.run_id"), [&KeyValue::new("gt.run_id", 7i64)]);
+        assert_eq!(get("gt.flow").len(), 1, "declared field recorded once");
+        assert!(get("gt.session").is_empty());
+    }
+
+    #[test]
+    fn never_entered_span_carries_context() {
+        let capture = CaptureProcessor::default();
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(capture.clone())
+            .build();
+        let tracer = ContextTracer {
+            inner: provider.tracer("layer-test"),
+            provider: Arc::new(acme),
+        };
+
+        let layers: Vec<BoxedLayer> = vec![
+            layer_with_provider(acme).boxed(),
+            tracing_opentelemetry::layer().with_tracer(tracer).boxed(),
+        ];
+        tracing::subscriber::with_default(Registry::default().with(layers), || {
+            let span = tracing::info_span!("never_entered", "gt.flow" = tracing::field::Empty);
+            drop(span);
+            let entered = tracing::info_span!("entered", "gt.flow" = tracing::field::Empty);
+            let _entered = entered.enter();
+        });
+
+        let spans = capture.spans.lock().expect("spans lock");
+        for name in ["never_entered", "entered"] {
+            let span = spans
+                .iter()
+                .find(|s| s.name == name)
+                .expect("span exported");
+            let get = |key: &str| {
+                span.attributes
+                    .iter()
+                    .filter(|kv| kv.key.as_str() == key)
+                    .collect::<Vec<&KeyValue>>()
+            };
+            assert_eq!(get("gt.tenant"), [&KeyValue::new("gt.tenant", "acme")]);
+            assert_eq!(get("gt.flow"), [&KeyValue::new("gt.flow", "intake")]);
+            assert_eq!(get("gt.run_id"), [&KeyValue::new("gt.run_id", 7i64)]);
+        }
+    }
+
+    #[test]
+    fn json_fmt_output_carries_context() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let fmt = tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(move || writer.clone());
+
+        let subscriber = Registry::default()
+            .with(layer_with_provider(acme))
+            .with(fmt);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("plain", step = 3);
+            let _entered = span.enter();
+            tracing::info!("inside");
+        });
+
+        let output = buffer.0.lock().expect("buffer lock");
+        let line: Value = serde_json::from_slice(&output).expect("json line");
+        assert_eq!(line["span"]["gt.tenant"], "acme");
+        assert_eq!(line["span"]["gt.flow"], "intake");
+        assert_eq!(line["span"]["gt.run_id"], 7);
+        assert_eq!(line["span"]["step"], 3);
+    }
+}
@@ -1,20 +1,58 @@
+pub mod builder;
 #[cfg(feature = "otlp")]
 pub mod client;
 pub mod context;
+pub mod export;
 #[cfg(feature = "otlp")]
 pub mod host_bridge;
 pub mod init;
+#[cfg(feature = "otlp")]
+mod json_stdout;
 pub mod layer;
+#[cfg(feature = "otlp")]
+mod logs;
+#[cfg(feature = "otlp")]
+pub mod metrics;
+#[cfg(feature = "tower")]
+pub mod middleware;
+pub mod presets;
+#[cfg(feature = "otlp")]
+mod prometheus;
+#[cfg(feature = "otlp")]
+pub mod propagation;
+pub mod redaction;
+pub mod sampling;
+pub mod span_metrics;
 pub mod tasklocal;
 pub mod testutil;
 
+pub use builder::{BoxedLayer, FmtStyle, TelemetryBuilder, TelemetryGuard};
 #[cfg(feature = "otlp")]
 pub use client::{init, metric, set_trace_id, span};
-pub use context::TelemetryCtx;
+pub use context::{
+    AttrValue, CloudCtx, CtxValue, ReservedAttrError, TelemetryCtx, context_snapshot, key_prefix,
+    set_context, set_key_prefix,
+};
+pub use export::{ExportConfig, ExportMode, Sampling};
 #[cfg(feature = "otlp")]
 pub use host_bridge::{HostContext, emit_span as emit_host_span};
 #[cfg(feature = "otlp")]
 pub use init::{OtlpConfig, TelemetryError, init_otlp};
 pub use init::{TelemetryConfig, init_telemetry, shutdown};
 pub use layer::{layer_from_task_local, layer_with_provider};
-pub use tasklocal::{set_current_telemetry_ctx, with_current_telemetry_ctx, with_task_local};
+#[cfg(feature = "otlp")]
+pub use metrics::{
+    Counter, Gauge, Histogram, Instrument, MetricAttributePolicy, MetricView, ObservableCounter,
+    ObservableGauge, ObservableUpDownCounter, Observer, UpDownCounter, counter, gauge, histogram,
+    instrument,
+};
+#[cfg(feature = "tower")]
+pub use middleware::{TelemetryLayer, TelemetryService};
+#[cfg(feature = "otlp")]
+pub use propagation::{
+    BaggagePolicy, Carrier, Propagator, extract_carrier, extract_carrier_into_span, inject_carrier,
+};
+pub use tasklocal::{
+    ContextGuard, WithCtx, WithTelemetryCtx, bind_current_telemetry_ctx, scope,
+    set_current_telemetry_ctx, spawn, spawn_blocking, with_current_telemetry_ctx, with_task_local,
+};
@@ -0,0 +1,130 @@
+//! Bridge from `tracing` events to OpenTelemetry log records.
+
+use std::time::Duration;
+
+use opentelemetry::InstrumentationScope;
+use opentelemetry::logs::LogRecord as _;
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    logs::{LogProcessor, SdkLogRecord, SdkLoggerProvider},
+};
+use tracing_subscriber::{Layer, filter::filter_fn};
+
+use crate::builder::BoxedLayer;
+use crate::tasklocal::with_current_telemetry_ctx;
+
+/// Targets emitted by the export pipeline itself; bridging them would feed
+/// exporter diagnostics back into the exporter.
+const EXPORTER_TARGETS: &[&str] = &["opentelemetry", "hyper", "h2", "tonic", "tower", "reqwest"];
+
+/// Stamps the task-local `TelemetryCtx` onto every log record before it is
+/// handed to the exporting processor.
+#[derive(Debug, Default)]
+pub(crate) struct ContextLogProcessor;
+
+impl LogProcessor for ContextLogProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+        with_current_telemetry_ctx(|ctx| {
+            if let Some(ctx) = ctx {
+                for (key, value) in ctx.kv() {
+                    record.add_attribute(key.into_owned(), value);
+                }
+            }
+        });
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// `tracing` layer that turns events into log records on `provider`.
+pub(crate) fn bridge_layer(provider: &SdkLoggerProvider) -> BoxedLayer {
+    OpenTelemetryTracingBridge::new(provider)
+        .with_filter(filter_fn(|meta| {
+            !EXPORTER_TARGETS
+                .iter()
+                .any(|target| meta.target().starts_with(target))
+        }))
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::TelemetryCtx;
+    use crate::tasklocal::{set_current_telemetry_ctx, with_task_local};
+    use opentelemetry::logs::{AnyValue, Severity};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    #[derive(Debug, Default, Clone)]
+    struct CaptureProcessor {
+        records: Arc<Mutex<Vec<SdkLogRecord>>>,
+    }
+
+    impl LogProcessor for CaptureProcessor {
+        fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+            self.records
+                .lock()
+                .expect("records lock")
+                .push(record.clone());
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn events_become_log_records_with_context() {
+        let capture = CaptureProcessor::default();
+        let logger_provider = SdkLoggerProvider::builder()
+            .with_log_processor(ContextLogProcessor)
+            .with_log_processor(capture.clone())
+            .build();
+        let tracer_provider = SdkTracerProvider::builder().build();
+        let tracer = tracer_provider.tracer("logs-test");
+
+        let layers: Vec<BoxedLayer> = vec![
+            tracing_opentelemetry::layer().with_tracer(tracer).boxed(),
+            bridge_layer(&logger_provider),
+        ];
+        let subscriber = Registry::default().with(layers);
+
+        with_task_local(async {
+            set_current_telemetry_ctx(TelemetryCtx::new("acme").with_flow("intake"));
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::info_span!("node");
+                let _entered = span.enter();
+                tracing::warn!(target: "greentic.runner", "node retried");
+                tracing::info!(target: "opentelemetry_sdk", "exporter noise");
+            });
+        })
+        .await;
+
+        let records = capture.records.lock().expect("records lock");
+        assert_eq!(records.len(), 1, "exporter targets must not be bridged");
+        let record = &records[0];
+
+        assert_eq!(record.severity_number(), Some(Severity::Warn));
+        assert_eq!(record.target().map(|t| t.as_ref()), Some("greentic.runner"));
+        assert!(record.trace_context().is_some(), "trace context missing");
+
+        let attr = |name: &str| {
+            record
+                .attributes_iter()
+                .find(|(key, _)| key.as_str() == name)
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(attr("gt.tenant"), Some(AnyValue::from("acme")));
+        assert_eq!(attr("gt.flow"), Some(AnyValue::from("intake")));
+    }
+}
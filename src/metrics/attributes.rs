@@ -0,0 +1,356 @@
+//! Which attributes become metric dimensions, and the cardinality limit
+//! that keeps each instrument's series bounded.
+
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::mem::{Discriminant, discriminant};
+use std::sync::{Arc, Mutex, RwLock};
+
+use once_cell::sync::Lazy;
+use opentelemetry::{KeyValue, Value};
+
+use super::exemplars::Exemplars;
+use crate::context::CtxValue;
+use crate::init::TELEMETRY_STATE;
+use crate::tasklocal::with_current_telemetry_ctx;
+
+/// Attribute of the series that absorbs measurements past the limit, as in
+/// the OpenTelemetry SDK.
+pub const OVERFLOW_KEY: &str = "otel.metric.overflow";
+
+/// The OpenTelemetry SDK default.
+pub const DEFAULT_CARDINALITY_LIMIT: usize = 2000;
+
+static DEFAULT_POLICY: Lazy<MetricAttributePolicy> = Lazy::new(MetricAttributePolicy::from_env);
+
+/// Series seen per instrument name, shared by every handle built under it.
+static SERIES: Lazy<Mutex<HashMap<String, Arc<Series>>>> = Lazy::new(Default::default);
+
+/// Which `TelemetryCtx` keys become dimensions of an instrument, and how
+/// many distinct attribute sets it may have.
+///
+/// Identifiers such as `gt.session` and `gt.run_id` are left out by default:
+/// every value would start a new series.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetricAttributePolicy {
+    /// Full keys such as `gt.tenant`; a trailing `*` matches a prefix.
+    pub context_keys: Vec<String>,
+    /// Attribute sets past this many are folded into one series tagged
+    /// `otel.metric.overflow=true`.
+    pub cardinality_limit: usize,
+}
+
+impl Default for MetricAttributePolicy {
+    fn default() -> Self {
+        let keys = crate::context::context_keys();
+        Self {
+            context_keys: ["tenant", "team", "flow", "node", "provider"]
+                .map(|name| keys.key(name))
+                .to_vec(),
+            cardinality_limit: DEFAULT_CARDINALITY_LIMIT,
+        }
+    }
+}
+
+impl MetricAttributePolicy {
+    /// Default policy with the context keys taken from
+    /// `TELEMETRY_METRIC_ATTRIBUTES` (comma-separated keys, empty for none)
+    /// and the limit from `TELEMETRY_METRIC_CARDINALITY_LIMIT`.
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Ok(keys) = std::env::var("TELEMETRY_METRIC_ATTRIBUTES") {
+            policy.context_keys = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        if let Ok(limit) = std::env::var("TELEMETRY_METRIC_CARDINALITY_LIMIT") {
+            match limit.trim().parse() {
+                Ok(limit) if limit > 0 => policy.cardinality_limit = limit,
+                _ => tracing::warn!(
+                    "ignoring TELEMETRY_METRIC_CARDINALITY_LIMIT '{limit}', expected a positive integer"
+                ),
+            }
+        }
+        policy
+    }
+
+    pub fn with_context_keys<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.context_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_cardinality_limit(mut self, limit: usize) -> Self {
+        self.cardinality_limit = limit.max(1);
+        self
+    }
+
+    pub fn allows(&self, key: &str) -> bool {
+        self.context_keys
+            .iter()
+            .any(|allowed| match allowed.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => allowed == key,
+            })
+    }
+}
+
+/// Attribute sets recorded so far by one instrument, and their exemplars.
+#[derive(Debug)]
+pub(crate) struct Series {
+    limit: usize,
+    seen: RwLock<HashSet<u64>>,
+    exemplars: Exemplars,
+}
+
+impl Series {
+    pub(crate) fn exemplars(&self) -> &Exemplars {
+        &self.exemplars
+    }
+
+    /// Whether the set hashed to `id` fits; new sets are admitted until the
+    /// limit, leaving one slot for the overflow series.
+    fn admit(&self, id: u64) -> bool {
+        if self
+            .seen
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains(&id)
+        {
+            return true;
+        }
+        let mut seen = self.seen.write().unwrap_or_else(|e| e.into_inner());
+        if seen.len() + 1 < self.limit || seen.contains(&id) {
+            seen.insert(id);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Attributes of one measurement, and the series they were admitted as.
+pub(crate) struct Point {
+    pub(crate) attributes: Vec<KeyValue>,
+    pub(crate) series: u64,
+}
+
+/// Series of the instruments built under `name`, if any.
+pub(crate) fn registered(name: &str) -> Option<Arc<Series>> {
+    SERIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .cloned()
+}
+
+/// Turns the service, context and caller attributes of a measurement into
+/// dimensions for one instrument.
+#[derive(Debug)]
+pub(crate) struct Dimensions {
+    policy: Option<MetricAttributePolicy>,
+    denied: Vec<String>,
+    series: Arc<Series>,
+}
+
+impl Dimensions {
+    /// The first instrument built under a name sets its limit; the views
+    /// registered by then decide the attributes it drops.
+    pub(crate) fn new(name: &str, policy: Option<MetricAttributePolicy>) -> Self {
+        let limit = policy.as_ref().unwrap_or(&DEFAULT_POLICY).cardinality_limit;
+        let series = SERIES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                Arc::new(Series {
+                    limit,
+                    seen: RwLock::default(),
+                    exemplars: Exemplars::default(),
+                })
+            })
+            .clone();
+        Self {
+            policy,
+            denied: super::views::denied_attributes(name),
+            series,
+        }
+    }
+
+    pub(crate) fn exemplars(&self) -> &Exemplars {
+        &self.series.exemplars
+    }
+
+    fn keeps(&self, key: &str) -> bool {
+        !self.denied.iter().any(|denied| denied == key)
+    }
+
+    pub(crate) fn point(&self, extra: &[KeyValue]) -> Point {
+        let mut attributes = service_attributes();
+        attributes.retain(|kv| self.keeps(kv.key.as_str()));
+        let service = attributes.len();
+        let policy = self.policy.as_ref().unwrap_or(&DEFAULT_POLICY);
+
+        if !policy.context_keys.is_empty() {
+            with_current_telemetry_ctx(|ctx| {
+                let Some(ctx) = ctx else { return };
+                let redaction = crate::redaction::policy_for(Some(&ctx.tenant));
+                let kept = |key: &str| policy.allows(key) && self.keeps(key);
+                for (key, value) in ctx.kv().filter(|(key, _)| kept(key)) {
+                    let value: Value = match value {
+                        CtxValue::Str(value) => match redaction.redact(&key, value) {
+                            Some(masked) => masked.into_owned().into(),
+                            None => continue,
+                        },
+                        other => other.into(),
+                    };
+                    attributes.push(KeyValue::new(key.into_owned(), value));
+                }
+            });
+        }
+        attributes.extend(
+            extra
+                .iter()
+                .filter(|kv| self.keeps(kv.key.as_str()))
+                .cloned(),
+        );
+
+        let series = set_id(&attributes);
+        if self.series.admit(series) {
+            return Point { attributes, series };
+        }
+        attributes.truncate(service);
+        attributes.push(KeyValue::new(OVERFLOW_KEY, true));
+        Point {
+            series: set_id(&attributes),
+            attributes,
+        }
+    }
+}
+
+fn service_attributes() -> Vec<KeyValue> {
+    let mut attrs = Vec::new();
+    if let Some(state) = TELEMETRY_STATE.get() {
+        attrs.push(KeyValue::new("service.name", state.service_name.clone()));
+        if let Some(version) = &state.service_version {
+            attrs.push(KeyValue::new("service.version", version.clone()));
+        }
+        if let Some(env) = &state.deployment_env {
+            attrs.push(KeyValue::new("deployment.environment", env.clone()));
+        }
+    }
+    attrs
+}
+
+/// Order-independent hash of an attribute set, as the SDK sorts attributes
+/// before aggregating.
+pub(crate) fn set_id(attributes: &[KeyValue]) -> u64 {
+    let mut sorted: Vec<(&str, Cow<'_, str>, Discriminant<Value>)> = attributes
+        .iter()
+        .map(|kv| (kv.key.as_str(), kv.value.as_str(), discriminant(&kv.value)))
+        .collect();
+    sorted.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    let mut hasher = DefaultHasher::new();
+    sorted.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_keys_and_prefixes() {
+        let policy = MetricAttributePolicy::default();
+        assert!(policy.allows("gt.tenant"));
+        assert!(!policy.allows("gt.session"));
+        assert!(!policy.allows("gt.run_id"));
+
+        let policy = policy.with_context_keys(["gt.tenant", "gt.llm.*"]);
+        assert!(policy.allows("gt.llm.model"));
+        assert!(!policy.allows("gt.flow"));
+    }
+
+    #[test]
+    fn sets_past_the_limit_overflow() {
+        let dimensions = Dimensions::new(
+            "test.attributes.overflow",
+            Some(MetricAttributePolicy::default().with_cardinality_limit(3)),
+        );
+        let point = |user: &str| dimensions.point(&[KeyValue::new("user", user.to_string())]);
+        let last = |point: &Point| point.attributes.last().cloned();
+
+        let first = point("a");
+        assert_eq!(last(&first), Some(KeyValue::new("user", "a")));
+        assert_eq!(last(&point("b")), Some(KeyValue::new("user", "b")));
+        let overflow = point("c");
+        assert_eq!(last(&overflow), Some(KeyValue::new(OVERFLOW_KEY, true)));
+        assert!(
+            overflow
+                .attributes
+                .iter()
+                .all(|kv| kv.key.as_str() != "user")
+        );
+        assert_eq!(point("d").series, overflow.series);
+        assert_eq!(point("a").series, first.series, "known sets still fit");
+    }
+
+    #[tokio::test]
+    async fn no_context_keys_and_poisoned_locks() {
+        use crate::context::TelemetryCtx;
+        use crate::tasklocal::{set_current_telemetry_ctx, with_task_local};
+
+        let dimensions = Arc::new(Dimensions::new(
+            "test.attributes.no_context",
+            Some(MetricAttributePolicy::default().with_context_keys(Vec::<String>::new())),
+        ));
+        let poisoner = dimensions.clone();
+        let _ = std::thread::spawn(move || {
+            let _seen = poisoner.series.seen.write().unwrap();
+            panic!("poison the series lock");
+        })
+        .join();
+        assert!(dimensions.series.seen.is_poisoned());
+
+        let point = with_task_local(async {
+            set_current_telemetry_ctx(TelemetryCtx::new("acme"));
+            dimensions.point(&[KeyValue::new("route", "/orders")])
+        })
+        .await;
+        assert!(
+            point
+                .attributes
+                .iter()
+                .all(|kv| kv.key.as_str() != "gt.tenant")
+        );
+        assert_eq!(
+            point.attributes.last(),
+            Some(&KeyValue::new("route", "/orders"))
+        );
+    }
+
+    #[test]
+    fn set_id_ignores_order() {
+        let (a, b) = (KeyValue::new("a", 1), KeyValue::new("b", "x"));
+        assert_eq!(set_id(&[a.clone(), b.clone()]), set_id(&[b, a]));
+    }
+
+    #[test]
+    fn set_id_separates_distinct_sets() {
+        let kv = |key: &'static str, value: &'static str| KeyValue::new(key, value);
+        assert_ne!(
+            set_id(&[kv("a", "1"), kv("b", "2")]),
+            set_id(&[kv("a", "2"), kv("b", "1")])
+        );
+        assert_ne!(set_id(&[kv("a", "1")]), set_id(&[KeyValue::new("a", 1)]));
+        assert_ne!(set_id(&[kv("ab", "c")]), set_id(&[kv("a", "bc")]));
+    }
+}
@@ -0,0 +1,81 @@
+//! Trace linkage for measurements, kept out of the attributes.
+//!
+//! Each series keeps the latest measurement taken inside a sampled span, in
+//! a slot owned by its instrument. The OpenTelemetry SDK does not yet attach
+//! exemplars to exported data points, so the Prometheus exporter reads them
+//! from here and serves them as OpenMetrics exemplars.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use opentelemetry::KeyValue;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceId};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::attributes::{Point, registered, set_id};
+
+/// A measurement and the span it was recorded in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exemplar {
+    /// Attributes of the series the measurement belongs to.
+    pub attributes: Vec<KeyValue>,
+    pub value: f64,
+    pub time: SystemTime,
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+}
+
+/// Latest exemplar of each series of one instrument.
+#[derive(Debug, Default)]
+pub(crate) struct Exemplars(Mutex<HashMap<u64, Exemplar>>);
+
+impl Exemplars {
+    /// Keep `value` as the exemplar of `point`'s series when `span_context`
+    /// is sampled.
+    pub(crate) fn offer(&self, point: &Point, value: f64, span_context: &SpanContext) {
+        if !span_context.is_valid() || !span_context.is_sampled() {
+            return;
+        }
+        let (time, trace_id, span_id) = (
+            SystemTime::now(),
+            span_context.trace_id(),
+            span_context.span_id(),
+        );
+        let mut series = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let exemplar = series.entry(point.series).or_insert_with(|| Exemplar {
+            attributes: point.attributes.clone(),
+            value,
+            time,
+            trace_id,
+            span_id,
+        });
+        exemplar.value = value;
+        exemplar.time = time;
+        exemplar.trace_id = trace_id;
+        exemplar.span_id = span_id;
+    }
+
+    /// The exemplar of the series with exactly `attributes`, in any order.
+    pub(crate) fn latest(&self, attributes: &[KeyValue]) -> Option<Exemplar> {
+        let series = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        series.get(&set_id(attributes)).cloned()
+    }
+
+    fn all(&self) -> Vec<Exemplar> {
+        let series = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        series.values().cloned().collect()
+    }
+}
+
+/// The latest exemplar of each series of `instrument`, in no particular order.
+pub fn exemplars(instrument: &str) -> Vec<Exemplar> {
+    registered(instrument)
+        .map(|series| series.exemplars().all())
+        .unwrap_or_default()
+}
+
+pub(crate) fn current_span_context() -> SpanContext {
+    Span::current().context().span().span_context().clone()
+}
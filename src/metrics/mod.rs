@@ -0,0 +1,486 @@
+//! Metric instruments that tag every measurement with the service and
+//! `TelemetryCtx` attributes.
+//!
+//! `counter`, `gauge` and `histogram` build `f64` instruments by name; use
+//! [`instrument`] for other value types, units, descriptions, attribute
+//! policies and observable (callback) instruments, and [`MetricView`] to
+//! change how their streams are aggregated.
+
+mod attributes;
+mod exemplars;
+mod views;
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use opentelemetry::KeyValue;
+use opentelemetry::global;
+use opentelemetry::metrics::{
+    AsyncInstrument, AsyncInstrumentBuilder, Counter as OtelCounter, Gauge as OtelGauge,
+    Histogram as OtelHistogram, HistogramBuilder, InstrumentBuilder, Meter,
+    ObservableCounter as OtelObservableCounter, ObservableGauge as OtelObservableGauge,
+    ObservableUpDownCounter as OtelObservableUpDownCounter, UpDownCounter as OtelUpDownCounter,
+};
+use opentelemetry::trace::SpanContext;
+
+use attributes::Dimensions;
+
+pub(crate) use attributes::registered;
+pub use attributes::{DEFAULT_CARDINALITY_LIMIT, MetricAttributePolicy, OVERFLOW_KEY};
+pub use exemplars::{Exemplar, exemplars};
+pub use views::{HistogramBuckets, MetricView, with_views};
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for f64 {}
+    impl Sealed for i64 {}
+    impl Sealed for u64 {}
+}
+
+/// Value types instruments record: `f64`, `i64` and `u64`.
+pub trait Number: Copy + sealed::Sealed {
+    #[doc(hidden)]
+    fn to_f64(self) -> f64;
+}
+
+impl Number for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Number for i64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Number for u64 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+/// Monotonic sum; `f64` or `u64`.
+#[derive(Clone, Debug)]
+pub struct Counter<T = f64> {
+    inner: Option<OtelCounter<T>>,
+    dimensions: Arc<Dimensions>,
+}
+
+impl<T: Number> Counter<T> {
+    pub fn add(&self, value: T) {
+        self.add_with(value, &[]);
+    }
+
+    /// Add with `extra` attributes alongside the service and context ones.
+    pub fn add_with(&self, value: T, extra: &[KeyValue]) {
+        if let Some(counter) = &self.inner {
+            counter.add(value, &measure(&self.dimensions, value, extra));
+        }
+    }
+
+    /// Like `add_with`, measured within `span` rather than the current span.
+    pub(crate) fn add_in(&self, value: T, extra: &[KeyValue], span: &SpanContext) {
+        if let Some(counter) = &self.inner {
+            counter.add(value, &measure_in(&self.dimensions, value, extra, span));
+        }
+    }
+}
+
+/// Sum that can go down, e.g. in-flight flow runs; `i64` or `f64`.
+#[derive(Clone, Debug)]
+pub struct UpDownCounter<T = i64> {
+    inner: Option<OtelUpDownCounter<T>>,
+    dimensions: Arc<Dimensions>,
+}
+
+impl<T: Number> UpDownCounter<T> {
+    pub fn add(&self, value: T) {
+        self.add_with(value, &[]);
+    }
+
+    /// Add with `extra` attributes alongside the service and context ones.
+    pub fn add_with(&self, value: T, extra: &[KeyValue]) {
+        if let Some(counter) = &self.inner {
+            counter.add(value, &measure(&self.dimensions, value, extra));
+        }
+    }
+}
+
+/// Last recorded value; `f64`, `i64` or `u64`.
+#[derive(Clone, Debug)]
+pub struct Gauge<T = f64> {
+    inner: Option<OtelGauge<T>>,
+    dimensions: Arc<Dimensions>,
+}
+
+impl<T: Number> Gauge<T> {
+    pub fn record(&self, value: T) {
+        self.record_with(value, &[]);
+    }
+
+    /// Record with `extra` attributes alongside the service and context ones.
+    pub fn record_with(&self, value: T, extra: &[KeyValue]) {
+        if let Some(gauge) = &self.inner {
+            gauge.record(value, &measure(&self.dimensions, value, extra));
+        }
+    }
+}
+
+/// Distribution of values; `f64` or `u64`.
+#[derive(Clone, Debug)]
+pub struct Histogram<T = f64> {
+    inner: Option<OtelHistogram<T>>,
+    dimensions: Arc<Dimensions>,
+}
+
+impl<T: Number> Histogram<T> {
+    pub fn record(&self, value: T) {
+        self.record_with(value, &[]);
+    }
+
+    /// Record with `extra` attributes alongside the service and context ones.
+    pub fn record_with(&self, value: T, extra: &[KeyValue]) {
+        if let Some(histogram) = &self.inner {
+            histogram.record(value, &measure(&self.dimensions, value, extra));
+        }
+    }
+
+    /// Like `record_with`, measured within `span` rather than the current span.
+    pub(crate) fn record_in(&self, value: T, extra: &[KeyValue], span: &SpanContext) {
+        if let Some(histogram) = &self.inner {
+            histogram.record(value, &measure_in(&self.dimensions, value, extra, span));
+        }
+    }
+}
+
+/// Handed to the callback of an observable instrument at each collection.
+/// Observations carry the service attributes and those of the
+/// `TelemetryCtx` current where the collection runs, usually the process one.
+pub struct Observer<'a, T> {
+    inner: &'a dyn AsyncInstrument<T>,
+    dimensions: &'a Dimensions,
+}
+
+impl<T> Observer<'_, T> {
+    pub fn observe(&self, value: T) {
+        self.observe_with(value, &[]);
+    }
+
+    /// Observe with `extra` attributes, e.g. one value per pool or queue.
+    pub fn observe_with(&self, value: T, extra: &[KeyValue]) {
+        self.inner
+            .observe(value, &self.dimensions.point(extra).attributes);
+    }
+}
+
+/// Gauge read through a callback, e.g. pool size or queue depth. The
+/// callback stays registered with the meter provider.
+#[derive(Clone, Debug)]
+pub struct ObservableGauge<T = f64> {
+    _inner: OtelObservableGauge<T>,
+}
+
+/// Monotonic sum read through a callback, e.g. bytes received so far.
+#[derive(Clone, Debug)]
+pub struct ObservableCounter<T = u64> {
+    _inner: OtelObservableCounter<T>,
+}
+
+/// Sum read through a callback that can go down, e.g. open connections.
+#[derive(Clone, Debug)]
+pub struct ObservableUpDownCounter<T = i64> {
+    _inner: OtelObservableUpDownCounter<T>,
+}
+
+/// Name, unit and description of an instrument, from [`instrument`].
+#[derive(Clone, Debug)]
+pub struct Instrument {
+    name: Cow<'static, str>,
+    unit: Option<Cow<'static, str>>,
+    description: Option<Cow<'static, str>>,
+    attributes: Option<MetricAttributePolicy>,
+}
+
+/// Describe an instrument named `name`, then build it with one of the typed
+/// methods:
+///
+/// ```no_run
+/// use greentic_telemetry::metrics::instrument;
+///
+/// let active = instrument("greentic.flow.runs.active")
+///     .with_unit("{run}")
+///     .with_description("Flow runs in progress")
+///     .i64_up_down_counter();
+/// active.add(1);
+///
+/// let _depth = instrument("greentic.queue.depth")
+///     .with_unit("{message}")
+///     .u64_observable_gauge(|observer| observer.observe(42));
+/// ```
+pub fn instrument(name: impl Into<Cow<'static, str>>) -> Instrument {
+    Instrument {
+        name: name.into(),
+        unit: None,
+        description: None,
+        attributes: None,
+    }
+}
+
+impl Instrument {
+    /// UCUM unit, e.g. `s`, `By` or `{request}`.
+    pub fn with_unit(mut self, unit: impl Into<Cow<'static, str>>) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<Cow<'static, str>>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Context keys and cardinality limit of this instrument, instead of
+    /// the process-wide [`MetricAttributePolicy::from_env`].
+    pub fn with_attribute_policy(mut self, policy: MetricAttributePolicy) -> Self {
+        self.attributes = Some(policy);
+        self
+    }
+
+    pub fn f64_counter(self) -> Counter<f64> {
+        let inner = self.sync(meter().f64_counter(self.name.clone())).build();
+        Counter {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn u64_counter(self) -> Counter<u64> {
+        let inner = self.sync(meter().u64_counter(self.name.clone())).build();
+        Counter {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn i64_up_down_counter(self) -> UpDownCounter<i64> {
+        let inner = self
+            .sync(meter().i64_up_down_counter(self.name.clone()))
+            .build();
+        UpDownCounter {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn f64_up_down_counter(self) -> UpDownCounter<f64> {
+        let inner = self
+            .sync(meter().f64_up_down_counter(self.name.clone()))
+            .build();
+        UpDownCounter {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn f64_gauge(self) -> Gauge<f64> {
+        let inner = self.sync(meter().f64_gauge(self.name.clone())).build();
+        Gauge {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn i64_gauge(self) -> Gauge<i64> {
+        let inner = self.sync(meter().i64_gauge(self.name.clone())).build();
+        Gauge {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn u64_gauge(self) -> Gauge<u64> {
+        let inner = self.sync(meter().u64_gauge(self.name.clone())).build();
+        Gauge {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn f64_histogram(self) -> Histogram<f64> {
+        let inner = self
+            .histogram(meter().f64_histogram(self.name.clone()))
+            .build();
+        Histogram {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn u64_histogram(self) -> Histogram<u64> {
+        let inner = self
+            .histogram(meter().u64_histogram(self.name.clone()))
+            .build();
+        Histogram {
+            inner: Some(inner),
+            dimensions: self.dimensions(),
+        }
+    }
+
+    pub fn f64_observable_gauge(
+        self,
+        callback: impl Fn(&Observer<'_, f64>) + Send + Sync + 'static,
+    ) -> ObservableGauge<f64> {
+        let meter = meter();
+        let builder = meter.f64_observable_gauge(self.name.clone());
+        ObservableGauge {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    pub fn i64_observable_gauge(
+        self,
+        callback: impl Fn(&Observer<'_, i64>) + Send + Sync + 'static,
+    ) -> ObservableGauge<i64> {
+        let meter = meter();
+        let builder = meter.i64_observable_gauge(self.name.clone());
+        ObservableGauge {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    pub fn u64_observable_gauge(
+        self,
+        callback: impl Fn(&Observer<'_, u64>) + Send + Sync + 'static,
+    ) -> ObservableGauge<u64> {
+        let meter = meter();
+        let builder = meter.u64_observable_gauge(self.name.clone());
+        ObservableGauge {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    pub fn u64_observable_counter(
+        self,
+        callback: impl Fn(&Observer<'_, u64>) + Send + Sync + 'static,
+    ) -> ObservableCounter<u64> {
+        let meter = meter();
+        let builder = meter.u64_observable_counter(self.name.clone());
+        ObservableCounter {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    pub fn f64_observable_counter(
+        self,
+        callback: impl Fn(&Observer<'_, f64>) + Send + Sync + 'static,
+    ) -> ObservableCounter<f64> {
+        let meter = meter();
+        let builder = meter.f64_observable_counter(self.name.clone());
+        ObservableCounter {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    pub fn i64_observable_up_down_counter(
+        self,
+        callback: impl Fn(&Observer<'_, i64>) + Send + Sync + 'static,
+    ) -> ObservableUpDownCounter<i64> {
+        let meter = meter();
+        let builder = meter.i64_observable_up_down_counter(self.name.clone());
+        ObservableUpDownCounter {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    pub fn f64_observable_up_down_counter(
+        self,
+        callback: impl Fn(&Observer<'_, f64>) + Send + Sync + 'static,
+    ) -> ObservableUpDownCounter<f64> {
+        let meter = meter();
+        let builder = meter.f64_observable_up_down_counter(self.name.clone());
+        ObservableUpDownCounter {
+            _inner: self.observable(builder, callback).build(),
+        }
+    }
+
+    fn dimensions(&self) -> Arc<Dimensions> {
+        Arc::new(Dimensions::new(&self.name, self.attributes.clone()))
+    }
+
+    fn sync<'a, T>(&self, mut builder: InstrumentBuilder<'a, T>) -> InstrumentBuilder<'a, T> {
+        if let Some(unit) = &self.unit {
+            builder = builder.with_unit(unit.clone());
+        }
+        if let Some(description) = &self.description {
+            builder = builder.with_description(description.clone());
+        }
+        builder
+    }
+
+    fn histogram<'a, T>(&self, mut builder: HistogramBuilder<'a, T>) -> HistogramBuilder<'a, T> {
+        if let Some(unit) = &self.unit {
+            builder = builder.with_unit(unit.clone());
+        }
+        if let Some(description) = &self.description {
+            builder = builder.with_description(description.clone());
+        }
+        builder
+    }
+
+    fn observable<'a, I, T: 'static>(
+        &self,
+        mut builder: AsyncInstrumentBuilder<'a, I, T>,
+        callback: impl Fn(&Observer<'_, T>) + Send + Sync + 'static,
+    ) -> AsyncInstrumentBuilder<'a, I, T> {
+        if let Some(unit) = &self.unit {
+            builder = builder.with_unit(unit.clone());
+        }
+        if let Some(description) = &self.description {
+            builder = builder.with_description(description.clone());
+        }
+        let dimensions = self.dimensions();
+        builder.with_callback(move |inner| {
+            callback(&Observer {
+                inner,
+                dimensions: &dimensions,
+            })
+        })
+    }
+}
+
+pub fn counter(name: impl Into<Cow<'static, str>>) -> Counter {
+    instrument(name).f64_counter()
+}
+
+pub fn gauge(name: impl Into<Cow<'static, str>>) -> Gauge {
+    instrument(name).f64_gauge()
+}
+
+pub fn histogram(name: impl Into<Cow<'static, str>>) -> Histogram {
+    instrument(name).f64_histogram()
+}
+
+fn meter() -> Meter {
+    global::meter("greentic-telemetry")
+}
+
+/// Attributes of a measurement, offering it as an exemplar of its series
+/// linked to the current span.
+fn measure<T: Number>(dimensions: &Dimensions, value: T, extra: &[KeyValue]) -> Vec<KeyValue> {
+    measure_in(dimensions, value, extra, &exemplars::current_span_context())
+}
+
+fn measure_in<T: Number>(
+    dimensions: &Dimensions,
+    value: T,
+    extra: &[KeyValue],
+    span_context: &SpanContext,
+) -> Vec<KeyValue> {
+    let point = dimensions.point(extra);
+    dimensions
+        .exemplars()
+        .offer(&point, value.to_f64(), span_context);
+    point.attributes
+}
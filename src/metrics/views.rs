@@ -0,0 +1,251 @@
+//! Views reshaping the streams of instruments matched by name, e.g.
+//! bucket boundaries suited to millisecond latencies or token counts.
+
+use std::sync::RwLock;
+
+use anyhow::{Result, anyhow};
+use globset::{Glob, GlobMatcher};
+use once_cell::sync::Lazy;
+use opentelemetry::Key;
+use opentelemetry_sdk::metrics::{
+    Aggregation, Instrument as SdkInstrument, InstrumentKind, MeterProviderBuilder, Stream,
+};
+
+/// The SDK bounds for base-2 exponential histograms.
+const MIN_SCALE: i8 = -10;
+const MAX_SCALE: i8 = 20;
+
+/// Views last registered with [`with_views`], consulted by the crate's
+/// instruments for the attributes to drop.
+static REGISTERED: Lazy<RwLock<Vec<MetricView>>> = Lazy::new(Default::default);
+
+/// How a matched histogram aggregates its values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HistogramBuckets {
+    /// Explicit, strictly increasing upper bounds.
+    Explicit(Vec<f64>),
+    /// Base-2 exponential buckets, rescaled to fit at most `max_size`
+    /// buckets starting from `max_scale` resolution.
+    Exponential { max_size: u32, max_scale: i8 },
+}
+
+/// Reshapes the instruments whose name matches a glob: bucket boundaries,
+/// exponential histograms, a new name, or the attributes kept or dropped.
+///
+/// ```
+/// use greentic_telemetry::metrics::MetricView;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let latency = MetricView::new("greentic.node.*.duration")?
+///     .with_buckets([1.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 1000.0]);
+/// let tokens = MetricView::new("greentic.llm.tokens")?.with_exponential_buckets(160, 20);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MetricView {
+    glob: String,
+    matcher: GlobMatcher,
+    name: Option<String>,
+    buckets: Option<HistogramBuckets>,
+    attributes: Option<Vec<String>>,
+    denied: Vec<String>,
+}
+
+impl MetricView {
+    /// Match instrument names against `glob`, e.g. `greentic.node.*`.
+    pub fn new(glob: &str) -> Result<Self> {
+        let matcher = Glob::new(glob)
+            .map_err(|err| anyhow!("invalid metric view glob '{glob}': {err}"))?
+            .compile_matcher();
+        Ok(Self {
+            glob: glob.to_string(),
+            matcher,
+            name: None,
+            buckets: None,
+            attributes: None,
+            denied: Vec::new(),
+        })
+    }
+
+    /// Export under `name` instead; match a single instrument, as streams
+    /// sharing a name conflict.
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Explicit bucket upper bounds for histograms.
+    pub fn with_buckets(mut self, boundaries: impl IntoIterator<Item = f64>) -> Self {
+        self.buckets = Some(HistogramBuckets::Explicit(boundaries.into_iter().collect()));
+        self
+    }
+
+    /// Base-2 exponential histograms; the SDK defaults are 160 buckets at
+    /// scale 20.
+    pub fn with_exponential_buckets(mut self, max_size: u32, max_scale: i8) -> Self {
+        self.buckets = Some(HistogramBuckets::Exponential {
+            max_size,
+            max_scale,
+        });
+        self
+    }
+
+    /// Keep only these attribute keys; the others are dropped before
+    /// aggregation. An empty list drops all of them.
+    pub fn with_attributes<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.attributes = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Drop these attribute keys before aggregation and keep the others.
+    ///
+    /// The SDK only filters streams by the keys to keep, so the keys are
+    /// dropped as the crate's [`instrument`](crate::metrics::instrument)s
+    /// measure; instruments built straight from an SDK meter keep them.
+    pub fn without_attributes<I, K>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.denied = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn glob(&self) -> &str {
+        &self.glob
+    }
+
+    pub fn matches(&self, instrument: &str) -> bool {
+        self.matcher.is_match(instrument)
+    }
+
+    /// Check the name and bucket settings, as the SDK otherwise ignores an
+    /// invalid view when the instrument is created.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(HistogramBuckets::Exponential { max_scale, .. }) = &self.buckets
+            && !(MIN_SCALE..=MAX_SCALE).contains(max_scale)
+        {
+            return Err(anyhow!(
+                "metric view '{}': exponential histogram scale {max_scale} is outside {MIN_SCALE}..={MAX_SCALE}",
+                self.glob
+            ));
+        }
+        self.stream(InstrumentKind::Histogram)
+            .map(drop)
+            .map_err(|err| anyhow!("metric view '{}': {err}", self.glob))
+    }
+
+    /// Buckets only apply to histograms; other settings to any instrument.
+    fn stream(&self, kind: InstrumentKind) -> Result<Stream, Box<dyn std::error::Error>> {
+        let mut stream = Stream::builder();
+        if let Some(name) = &self.name {
+            stream = stream.with_name(name.clone());
+        }
+        match &self.buckets {
+            Some(HistogramBuckets::Explicit(boundaries)) if kind == InstrumentKind::Histogram => {
+                stream = stream.with_aggregation(Aggregation::ExplicitBucketHistogram {
+                    boundaries: boundaries.clone(),
+                    record_min_max: true,
+                });
+            }
+            Some(HistogramBuckets::Exponential {
+                max_size,
+                max_scale,
+            }) if kind == InstrumentKind::Histogram => {
+                stream = stream.with_aggregation(Aggregation::Base2ExponentialHistogram {
+                    max_size: *max_size,
+                    max_scale: *max_scale,
+                    record_min_max: true,
+                });
+            }
+            _ => {}
+        }
+        if let Some(keys) = &self.attributes {
+            stream = stream.with_allowed_attribute_keys(keys.iter().cloned().map(Key::from));
+        }
+        stream.build()
+    }
+}
+
+/// Register `views` on a meter provider; the first view matching an
+/// instrument applies, and unmatched instruments keep the default stream.
+///
+/// [`crate::TelemetryBuilder::with_metric_view`] does this for the provider
+/// the crate builds; use it directly when building your own.
+pub fn with_views(
+    builder: MeterProviderBuilder,
+    views: impl IntoIterator<Item = MetricView>,
+) -> MeterProviderBuilder {
+    let views: Vec<_> = views.into_iter().collect();
+    *REGISTERED.write().unwrap_or_else(|e| e.into_inner()) = views.clone();
+    if views.is_empty() {
+        return builder;
+    }
+    builder.with_view(move |instrument: &SdkInstrument| {
+        let view = views.iter().find(|view| view.matches(instrument.name()))?;
+        match view.stream(instrument.kind()) {
+            Ok(stream) => Some(stream),
+            Err(err) => {
+                tracing::warn!("ignoring metric view '{}': {err}", view.glob);
+                None
+            }
+        }
+    })
+}
+
+/// Attribute keys dropped from `instrument` by the first registered view
+/// matching it.
+pub(crate) fn denied_attributes(instrument: &str) -> Vec<String> {
+    REGISTERED
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|view| view.matches(instrument))
+        .map(|view| view.denied.clone())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs_and_validates_buckets() {
+        let view = MetricView::new("greentic.node.*").unwrap();
+        assert!(view.matches("greentic.node.duration"));
+        assert!(!view.matches("greentic.flow.duration"));
+        assert!(MetricView::new("greentic.[").is_err());
+
+        assert!(
+            view.clone()
+                .with_buckets([1.0, 5.0, 10.0])
+                .validate()
+                .is_ok()
+        );
+        assert!(view.clone().with_buckets([5.0, 1.0]).validate().is_err());
+        assert!(
+            view.clone()
+                .with_buckets([1.0, f64::NAN])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            view.clone()
+                .with_exponential_buckets(160, 20)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            view.clone()
+                .with_exponential_buckets(160, 21)
+                .validate()
+                .is_err()
+        );
+        assert!(view.with_name("").validate().is_err());
+    }
+}
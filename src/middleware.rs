@@ -0,0 +1,255 @@
+//! `tower` middleware that traces HTTP and gRPC requests.
+//!
+//! [`TelemetryLayer::server`] opens a `SpanKind::Server` span per request,
+//! parents it on the incoming trace context and runs the inner service with
+//! the caller's `TelemetryCtx`. [`TelemetryLayer::client`] opens a
+//! `SpanKind::Client` span and injects the trace context and baggage into
+//! the outgoing headers. Both record the response status and duration.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::Instant;
+
+use http::uri::Authority;
+use http::{Request, Response};
+use opentelemetry::KeyValue;
+use pin_project_lite::pin_project;
+use tower::{Layer, Service};
+use tracing::Span;
+use tracing::field::Empty;
+
+use crate::metrics::{Histogram, histogram};
+use crate::propagation::{extract_remote, inject_carrier};
+use crate::tasklocal::{ContextGuard, WithCtx, WithTelemetryCtx, overlay_current};
+
+const SERVER_DURATION: &str = "http.server.request.duration";
+const CLIENT_DURATION: &str = "http.client.request.duration";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Server,
+    Client,
+}
+
+/// Layer producing a [`TelemetryService`]; see the module docs.
+#[derive(Clone, Debug)]
+pub struct TelemetryLayer {
+    kind: Kind,
+}
+
+impl TelemetryLayer {
+    /// Trace incoming requests.
+    pub fn server() -> Self {
+        Self { kind: Kind::Server }
+    }
+
+    /// Trace outgoing requests.
+    pub fn client() -> Self {
+        Self { kind: Kind::Client }
+    }
+}
+
+impl<S> Layer<S> for TelemetryLayer {
+    type Service = TelemetryService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let name = match self.kind {
+            Kind::Server => SERVER_DURATION,
+            Kind::Client => CLIENT_DURATION,
+        };
+        TelemetryService {
+            inner,
+            kind: self.kind,
+            duration: histogram(name),
+        }
+    }
+}
+
+/// Service traced by [`TelemetryLayer`].
+#[derive(Clone, Debug)]
+pub struct TelemetryService<S> {
+    inner: S,
+    kind: Kind,
+    duration: Histogram,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TelemetryService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = WithCtx<ResponseFuture<S::Future>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let span = match self.kind {
+            Kind::Server => server_span(&req),
+            Kind::Client => client_span(&req),
+        };
+        // The caller's context, layered over ours, for the whole request.
+        let ctx = match self.kind {
+            Kind::Server => Some(extract_remote(req.headers(), &span)),
+            Kind::Client => None,
+        }
+        .filter(|remote| remote.kv().next().is_some())
+        .map(|remote| overlay_current(&remote));
+
+        let _entered = span.enter();
+        if self.kind == Kind::Client {
+            inject_carrier(req.headers_mut());
+        }
+        let _ctx = ctx.clone().map(|ctx| ContextGuard::replace_sync(Some(ctx)));
+
+        let method = KeyValue::new("http.request.method", req.method().as_str().to_string());
+        let start = Instant::now();
+        let future = ResponseFuture {
+            inner: self.inner.call(req),
+            span: span.clone(),
+            kind: self.kind,
+            method,
+            duration: self.duration.clone(),
+            start,
+        };
+        match ctx {
+            Some(ctx) => future.with_telemetry_ctx(ctx),
+            None => future.in_current_telemetry_ctx(),
+        }
+    }
+}
+
+fn server_span<B>(req: &Request<B>) -> Span {
+    let method = req.method().as_str();
+    let span = tracing::info_span!(
+        target: "greentic.telemetry.http",
+        "HTTP request",
+        otel.name = method,
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = method,
+        url.path = req.uri().path(),
+        url.query = Empty,
+        url.scheme = Empty,
+        server.address = Empty,
+        server.port = Empty,
+        user_agent.original = Empty,
+        network.protocol.version = ?req.version(),
+        http.response.status_code = Empty,
+        error.type = Empty,
+    );
+    if let Some(query) = req.uri().query() {
+        span.record("url.query", query);
+    }
+    if let Some(scheme) = req.uri().scheme_str() {
+        span.record("url.scheme", scheme);
+    }
+    let authority = header(req, http::header::HOST)
+        .and_then(|host| host.parse::<Authority>().ok())
+        .or_else(|| req.uri().authority().cloned());
+    if let Some(authority) = authority {
+        span.record("server.address", authority.host());
+        if let Some(port) = authority.port_u16() {
+            span.record("server.port", i64::from(port));
+        }
+    }
+    if let Some(agent) = header(req, http::header::USER_AGENT) {
+        span.record("user_agent.original", agent);
+    }
+    span
+}
+
+fn client_span<B>(req: &Request<B>) -> Span {
+    let method = req.method().as_str();
+    let span = tracing::info_span!(
+        target: "greentic.telemetry.http",
+        "HTTP request",
+        otel.name = method,
+        otel.kind = "client",
+        otel.status_code = Empty,
+        http.request.method = method,
+        url.full = %req.uri(),
+        server.address = Empty,
+        server.port = Empty,
+        http.response.status_code = Empty,
+        error.type = Empty,
+    );
+    if let Some(host) = req.uri().host() {
+        span.record("server.address", host);
+    }
+    if let Some(port) = req.uri().port_u16() {
+        span.record("server.port", i64::from(port));
+    }
+    span
+}
+
+fn header<B>(req: &Request<B>, name: http::header::HeaderName) -> Option<&str> {
+    req.headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+pin_project! {
+    /// Response future of [`TelemetryService`]; records status and duration
+    /// when the inner future completes.
+    #[derive(Debug)]
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+        kind: Kind,
+        method: KeyValue,
+        duration: Histogram,
+        start: Instant,
+    }
+}
+
+impl<F, B, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<B>, E>>,
+    E: Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.inner.poll(cx));
+
+        let mut attrs = vec![this.method.clone()];
+        match &result {
+            Ok(response) => {
+                let status = response.status();
+                this.span
+                    .record("http.response.status_code", i64::from(status.as_u16()));
+                attrs.push(KeyValue::new(
+                    "http.response.status_code",
+                    i64::from(status.as_u16()),
+                ));
+                let failed = match this.kind {
+                    Kind::Server => status.is_server_error(),
+                    Kind::Client => status.is_client_error() || status.is_server_error(),
+                };
+                if failed {
+                    this.span.record("otel.status_code", "ERROR");
+                    this.span.record("error.type", status.as_str());
+                    attrs.push(KeyValue::new("error.type", status.as_str().to_string()));
+                }
+            }
+            Err(err) => {
+                this.span.record("otel.status_code", "ERROR");
+                this.span.record("error.type", "_OTHER");
+                attrs.push(KeyValue::new("error.type", "_OTHER"));
+                tracing::debug!(target: "greentic.telemetry.http", error = %err, "request failed");
+            }
+        }
+        this.duration
+            .record_with(this.start.elapsed().as_secs_f64(), &attrs);
+
+        Poll::Ready(result)
+    }
+}
@@ -0,0 +1,613 @@
+//! Metrics served on `/metrics` in the Prometheus text exposition format,
+//! for deployments that scrape instead of running a collector.
+//!
+//! Scrapers that accept OpenMetrics get that format instead, with the
+//! latest exemplar of each counter and histogram series linking it to a
+//! trace.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read as _, Write as _};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use opentelemetry::KeyValue;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::reader::MetricReader;
+use opentelemetry_sdk::metrics::{InstrumentKind, ManualReader, Pipeline, Temporality};
+
+use crate::metrics::{Exemplar, Number, registered};
+
+const TEXT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Request line and headers past this size are refused.
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+/// Scrapes answered at once; connections past this are closed unanswered.
+const MAX_CONCURRENT_SCRAPES: usize = 4;
+
+/// Collects from the meter provider on each scrape; shares its reader with
+/// the provider it is registered on.
+#[derive(Clone, Debug)]
+pub(crate) struct PrometheusReader(Arc<ManualReader>);
+
+impl MetricReader for PrometheusReader {
+    fn register_pipeline(&self, pipeline: Weak<Pipeline>) {
+        self.0.register_pipeline(pipeline);
+    }
+
+    fn collect(&self, rm: &mut ResourceMetrics) -> OTelSdkResult {
+        self.0.collect(rm)
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn temporality(&self, kind: InstrumentKind) -> Temporality {
+        self.0.temporality(kind)
+    }
+}
+
+/// Exposition format of a scrape response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// Prometheus text format 0.0.4, which has no exemplars.
+    Text,
+    /// OpenMetrics 1.0, with exemplars on counters and histogram buckets.
+    OpenMetrics,
+}
+
+impl Format {
+    /// OpenMetrics when the `Accept` header lists it, as Prometheus does.
+    fn negotiate(accept: Option<&str>) -> Self {
+        match accept {
+            Some(accept) if accept.contains("application/openmetrics-text") => Format::OpenMetrics,
+            _ => Format::Text,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Text => TEXT_CONTENT_TYPE,
+            Format::OpenMetrics => OPENMETRICS_CONTENT_TYPE,
+        }
+    }
+}
+
+/// The listener thread behind `/metrics`.
+#[derive(Clone, Debug)]
+pub(crate) struct PrometheusServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl PrometheusServer {
+    /// Bind `addr` and answer each scrape on its own short-lived thread, so
+    /// a slow client does not hold up the others, up to
+    /// `MAX_CONCURRENT_SCRAPES` at once; register the returned reader on the
+    /// meter provider.
+    pub(crate) fn serve(addr: &str) -> Result<(Self, PrometheusReader)> {
+        let listener = TcpListener::bind(addr)
+            .with_context(|| format!("failed to bind the Prometheus listener on {addr}"))?;
+        let server = Self {
+            addr: listener.local_addr()?,
+            stopped: Arc::default(),
+        };
+        let reader = PrometheusReader(Arc::new(ManualReader::builder().build()));
+
+        let (stopped, scrape) = (server.stopped.clone(), reader.clone());
+        let active = Arc::new(AtomicUsize::new(0));
+        std::thread::Builder::new()
+            .name("prometheus-exporter".into())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::Acquire) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let Some(slot) = ScrapeSlot::acquire(&active) else {
+                        tracing::debug!("prometheus scrape refused: too many in flight");
+                        continue;
+                    };
+                    let scrape = scrape.clone();
+                    let spawned = std::thread::Builder::new()
+                        .name("prometheus-scrape".into())
+                        .spawn(move || {
+                            let _slot = slot;
+                            if let Err(err) = respond(stream, &scrape) {
+                                tracing::debug!("prometheus scrape failed: {err}");
+                            }
+                        });
+                    if let Err(err) = spawned {
+                        tracing::debug!("prometheus scrape not served: {err}");
+                    }
+                }
+            })?;
+        Ok((server, reader))
+    }
+
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting scrapes; wakes the listener with a last connection.
+    pub(crate) fn shutdown(&self) {
+        if !self.stopped.swap(true, Ordering::AcqRel) {
+            let _ = TcpStream::connect_timeout(&self.addr, Duration::from_millis(100));
+        }
+    }
+}
+
+/// One of the `MAX_CONCURRENT_SCRAPES` slots, released when dropped.
+struct ScrapeSlot(Arc<AtomicUsize>);
+
+impl ScrapeSlot {
+    fn acquire(active: &Arc<AtomicUsize>) -> Option<Self> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < MAX_CONCURRENT_SCRAPES).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| Self(active.clone()))
+    }
+}
+
+impl Drop for ScrapeSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+fn respond(mut stream: TcpStream, reader: &PrometheusReader) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut request = BufReader::new(&stream).take(MAX_REQUEST_BYTES);
+    let mut request_line = String::new();
+    request.read_line(&mut request_line)?;
+    // Drain the headers; only `Accept` changes the response.
+    let mut accept = None;
+    let mut line = String::new();
+    while request.read_line(&mut line)? > 2 {
+        if let Some((name, value)) = line.split_once(':')
+            && name.trim().eq_ignore_ascii_case("accept")
+        {
+            accept = Some(value.trim().to_string());
+        }
+        line.clear();
+    }
+    let format = Format::negotiate(accept.as_deref());
+    let truncated = request.limit() == 0;
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (
+        parts.next(),
+        parts.next().map(|path| path.split('?').next()),
+    );
+    let (status, body) = match (method, path) {
+        _ if truncated => (
+            "431 Request Header Fields Too Large",
+            "request too large\n".to_string(),
+        ),
+        (Some("GET"), Some(Some("/metrics"))) => {
+            let mut metrics = ResourceMetrics::default();
+            match reader.collect(&mut metrics) {
+                Ok(()) => ("200 OK", render(&metrics, format)),
+                Err(err) => ("503 Service Unavailable", format!("{err}\n")),
+            }
+        }
+        (Some("GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => ("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        format.content_type(),
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// One `# TYPE` block; instruments of several meters may share it.
+struct Family {
+    kind: &'static str,
+    help: String,
+    samples: String,
+}
+
+/// Render `metrics` in `format`, with the resource as `target_info`.
+/// Exponential histograms have no text representation and are left out.
+pub(crate) fn render(metrics: &ResourceMetrics, format: Format) -> String {
+    let mut families: BTreeMap<String, Family> = BTreeMap::new();
+    for metric in metrics.scope_metrics().flat_map(|scope| scope.metrics()) {
+        match metric.data() {
+            AggregatedMetrics::F64(data) => render_metric(&mut families, metric, data, format),
+            AggregatedMetrics::U64(data) => render_metric(&mut families, metric, data, format),
+            AggregatedMetrics::I64(data) => render_metric(&mut families, metric, data, format),
+        }
+    }
+
+    let mut out = String::new();
+    render_target_info(&mut out, metrics.resource(), format);
+    for (name, family) in families {
+        if !family.help.is_empty() {
+            let _ = writeln!(out, "# HELP {name} {}", escape_help(&family.help, format));
+        }
+        let _ = writeln!(out, "# TYPE {name} {}", family.kind);
+        out.push_str(&family.samples);
+    }
+    if format == Format::OpenMetrics {
+        out.push_str("# EOF\n");
+    }
+    out
+}
+
+fn render_target_info(out: &mut String, resource: &Resource, format: Format) {
+    let attributes: Vec<KeyValue> = resource
+        .iter()
+        .map(|(key, value)| KeyValue::new(key.clone(), value.clone()))
+        .collect();
+    if attributes.is_empty() {
+        return;
+    }
+    out.push_str(match format {
+        Format::Text => "# HELP target_info Target metadata\n# TYPE target_info gauge\n",
+        Format::OpenMetrics => "# HELP target Target metadata\n# TYPE target info\n",
+    });
+    let _ = writeln!(out, "target_info{} 1", labels(attributes.iter(), None));
+}
+
+fn render_metric<T: Number>(
+    families: &mut BTreeMap<String, Family>,
+    metric: &Metric,
+    data: &MetricData<T>,
+    format: Format,
+) {
+    let base = metric_name(metric.name(), metric.unit());
+    let (family_name, name, kind) = match data {
+        MetricData::Sum(sum) if sum.is_monotonic() => {
+            let base = base.strip_suffix("_total").unwrap_or(&base);
+            let total = format!("{base}_total");
+            match format {
+                Format::Text => (total.clone(), total, "counter"),
+                Format::OpenMetrics => (base.to_string(), total, "counter"),
+            }
+        }
+        MetricData::Sum(_) | MetricData::Gauge(_) => (base.clone(), base, "gauge"),
+        MetricData::Histogram(_) => (base.clone(), base, "histogram"),
+        MetricData::ExponentialHistogram(_) => {
+            warn_unrendered(metric.name());
+            return;
+        }
+    };
+    // Exemplars are kept per instrument; OpenMetrics allows them on counters
+    // and histogram buckets only.
+    let exemplars = match (format, kind) {
+        (Format::OpenMetrics, "counter" | "histogram") => registered(metric.name()),
+        _ => None,
+    };
+    let exemplar = |attributes: &mut dyn Iterator<Item = &KeyValue>| {
+        let series = exemplars.as_ref()?;
+        series
+            .exemplars()
+            .latest(&attributes.cloned().collect::<Vec<_>>())
+    };
+    let family = families.entry(family_name).or_insert_with(|| Family {
+        kind,
+        help: metric.description().to_string(),
+        samples: String::new(),
+    });
+    if family.kind != kind {
+        tracing::debug!("skipping {name}: already exported as a {}", family.kind);
+        return;
+    }
+    let out = &mut family.samples;
+
+    match data {
+        MetricData::Sum(sum) => {
+            for point in sum.data_points() {
+                let linked = exemplar(&mut point.attributes());
+                let value = point.value().to_f64();
+                sample(out, &name, point.attributes(), None, value, linked.as_ref());
+            }
+        }
+        MetricData::Gauge(gauge) => {
+            for point in gauge.data_points() {
+                let value = point.value().to_f64();
+                sample(out, &name, point.attributes(), None, value, None);
+            }
+        }
+        MetricData::Histogram(histogram) => {
+            for point in histogram.data_points() {
+                let bucket = format!("{name}_bucket");
+                let mut linked = exemplar(&mut point.attributes());
+                let mut cumulative = 0;
+                let counts = point.bucket_counts();
+                let bounds = point.bounds().map(Some).chain([None]);
+                for (bound, count) in bounds.zip(counts) {
+                    cumulative += count;
+                    let le = bound.map_or_else(|| "+Inf".to_string(), number);
+                    // Each exemplar goes on the first bucket that holds it.
+                    let in_bucket =
+                        linked.take_if(|linked| bound.is_none_or(|bound| linked.value <= bound));
+                    sample(
+                        out,
+                        &bucket,
+                        point.attributes(),
+                        Some(("le", le.as_str())),
+                        cumulative as f64,
+                        in_bucket.as_ref(),
+                    );
+                }
+                let sum = point.sum().to_f64();
+                sample(
+                    out,
+                    &format!("{name}_sum"),
+                    point.attributes(),
+                    None,
+                    sum,
+                    None,
+                );
+                let count = point.count() as f64;
+                sample(
+                    out,
+                    &format!("{name}_count"),
+                    point.attributes(),
+                    None,
+                    count,
+                    None,
+                );
+            }
+        }
+        MetricData::ExponentialHistogram(_) => {}
+    }
+}
+
+/// Warn once per instrument that its exponential histogram is not served.
+fn warn_unrendered(instrument: &str) {
+    static WARNED: Lazy<Mutex<HashSet<String>>> = Lazy::new(Default::default);
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned.insert(instrument.to_string()) {
+        tracing::warn!(
+            "metric '{instrument}' is an exponential histogram, which Prometheus text cannot carry; \
+             it is left out of /metrics, use explicit buckets in its view instead"
+        );
+    }
+}
+
+fn sample<'a>(
+    out: &mut String,
+    name: &str,
+    attributes: impl Iterator<Item = &'a KeyValue>,
+    extra: Option<(&str, &str)>,
+    value: f64,
+    exemplar: Option<&Exemplar>,
+) {
+    let _ = write!(out, "{name}{} {}", labels(attributes, extra), number(value));
+    if let Some(exemplar) = exemplar {
+        let time = exemplar
+            .time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |time| time.as_secs_f64());
+        let _ = write!(
+            out,
+            " # {{trace_id=\"{}\",span_id=\"{}\"}} {} {time:.3}",
+            exemplar.trace_id,
+            exemplar.span_id,
+            number(exemplar.value)
+        );
+    }
+    out.push('\n');
+}
+
+fn labels<'a>(
+    attributes: impl Iterator<Item = &'a KeyValue>,
+    extra: Option<(&str, &str)>,
+) -> String {
+    // Keys that sanitize to the same name share one label, their values
+    // joined with `;` in the order of the original keys.
+    let mut sorted: Vec<(String, &str, String)> = attributes
+        .map(|kv| {
+            let key = kv.key.as_str();
+            (label_name(key), key, kv.value.as_str().into_owned())
+        })
+        .collect();
+    sorted.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+    let mut pairs: Vec<(String, String)> = Vec::with_capacity(sorted.len() + 1);
+    for (name, _, value) in sorted {
+        match pairs.last_mut() {
+            Some((last, joined)) if *last == name => {
+                joined.push(';');
+                joined.push_str(&value);
+            }
+            _ => pairs.push((name, value)),
+        }
+    }
+    if let Some((key, value)) = extra {
+        pairs.push((key.to_string(), value.to_string()));
+    }
+    if pairs.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = pairs
+        .into_iter()
+        .map(|(key, value)| format!("{key}=\"{}\"", escape_label(&value)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Name with characters Prometheus rejects replaced, and the unit appended
+/// as in the OpenTelemetry to Prometheus compatibility spec.
+fn metric_name(name: &str, unit: &str) -> String {
+    let mut name = sanitize(name);
+    let unit = match unit {
+        "s" => "seconds",
+        "ms" => "milliseconds",
+        "us" => "microseconds",
+        "ns" => "nanoseconds",
+        "By" => "bytes",
+        "KiBy" => "kibibytes",
+        "MiBy" => "mebibytes",
+        "%" => "percent",
+        // Annotations such as `{request}` and the unit `1` add no suffix.
+        unit if unit.starts_with('{') || unit == "1" => "",
+        unit => unit,
+    };
+    let unit = sanitize(unit);
+    if !unit.is_empty() && !name.ends_with(&format!("_{unit}")) {
+        name = format!("{name}_{unit}");
+    }
+    name
+}
+
+/// Metric names may contain `:`; label names may not.
+fn sanitize(name: &str) -> String {
+    sanitize_with(name, |c| c == ':')
+}
+
+fn label_name(key: &str) -> String {
+    sanitize_with(key, |_| false)
+}
+
+fn sanitize_with(name: &str, also_valid: impl Fn(char) -> bool) -> String {
+    let mut out: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || also_valid(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+fn number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn escape_help(value: &str, format: Format) -> String {
+    let escaped = value.replace('\\', r"\\").replace('\n', r"\n");
+    match format {
+        Format::Text => escaped,
+        Format::OpenMetrics => escaped.replace('"', "\\\""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::SdkMeterProvider;
+
+    use super::*;
+
+    #[test]
+    fn renders_counters_gauges_histograms_and_target_info() {
+        let reader = PrometheusReader(Arc::new(ManualReader::builder().build()));
+        let provider = SdkMeterProvider::builder()
+            .with_reader(reader.clone())
+            .with_resource(Resource::builder().with_service_name("runner").build())
+            .build();
+        let meter = provider.meter("test");
+        meter
+            .u64_counter("greentic.flow.runs")
+            .with_description("Completed runs")
+            .build()
+            .add(3, &[KeyValue::new("gt.tenant", "ac\"me")]);
+        meter
+            .i64_up_down_counter("greentic.runs.active")
+            .build()
+            .add(2, &[]);
+        let latency = meter
+            .f64_histogram("greentic.node.duration")
+            .with_unit("ms")
+            .with_boundaries(vec![10.0, 100.0])
+            .build();
+        latency.record(5.0, &[]);
+        latency.record(50.0, &[]);
+
+        let mut metrics = ResourceMetrics::default();
+        reader.collect(&mut metrics).unwrap();
+        let text = render(&metrics, Format::Text);
+
+        assert!(text.contains("# TYPE target_info gauge\n"), "{text}");
+        assert!(text.contains("service_name=\"runner\""), "{text}");
+        assert!(
+            text.contains("# HELP greentic_flow_runs_total Completed runs\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("# TYPE greentic_flow_runs_total counter\n"),
+            "{text}"
+        );
+        assert!(
+            text.contains("greentic_flow_runs_total{gt_tenant=\"ac\\\"me\"} 3\n"),
+            "{text}"
+        );
+        assert!(text.contains("# TYPE greentic_runs_active gauge\ngreentic_runs_active 2\n"));
+        assert!(text.contains("# TYPE greentic_node_duration_milliseconds histogram\n"));
+        for line in [
+            "greentic_node_duration_milliseconds_bucket{le=\"10\"} 1\n",
+            "greentic_node_duration_milliseconds_bucket{le=\"100\"} 2\n",
+            "greentic_node_duration_milliseconds_bucket{le=\"+Inf\"} 2\n",
+            "greentic_node_duration_milliseconds_sum 55\n",
+            "greentic_node_duration_milliseconds_count 2\n",
+        ] {
+            assert!(text.contains(line), "{line} in {text}");
+        }
+    }
+
+    #[test]
+    fn names_follow_prometheus_conventions() {
+        assert_eq!(
+            metric_name("http.server.duration", "s"),
+            "http_server_duration_seconds"
+        );
+        assert_eq!(metric_name("queue.depth", "{message}"), "queue_depth");
+        assert_eq!(metric_name("payload_bytes", "By"), "payload_bytes");
+        assert_eq!(sanitize("9lives-x"), "_9lives_x");
+        assert_eq!(sanitize("job:requests"), "job:requests");
+        assert_eq!(label_name("k8s.pod:name"), "k8s_pod_name");
+    }
+
+    #[test]
+    fn colliding_label_names_are_merged() {
+        let attributes = [
+            KeyValue::new("a_b", "2"),
+            KeyValue::new("ns:key", "x"),
+            KeyValue::new("a.b", "1"),
+        ];
+        assert_eq!(
+            labels(attributes.iter(), Some(("le", "10"))),
+            "{a_b=\"1;2\",ns_key=\"x\",le=\"10\"}"
+        );
+    }
+}
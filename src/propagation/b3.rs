@@ -0,0 +1,227 @@
+//! Zipkin B3 propagation in single-header (`b3`) and multi-header
+//! (`X-B3-*`) form.
+
+use once_cell::sync::Lazy;
+use opentelemetry::{
+    Context,
+    propagation::{Extractor, Injector, TextMapPropagator, text_map_propagator::FieldIter},
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+};
+
+const B3_SINGLE: &str = "b3";
+const B3_TRACE_ID: &str = "x-b3-traceid";
+const B3_SPAN_ID: &str = "x-b3-spanid";
+const B3_PARENT_SPAN_ID: &str = "x-b3-parentspanid";
+const B3_SAMPLED: &str = "x-b3-sampled";
+const B3_FLAGS: &str = "x-b3-flags";
+
+static SINGLE_FIELDS: Lazy<[String; 1]> = Lazy::new(|| [B3_SINGLE.to_string()]);
+static MULTI_FIELDS: Lazy<[String; 5]> = Lazy::new(|| {
+    [
+        B3_TRACE_ID,
+        B3_SPAN_ID,
+        B3_PARENT_SPAN_ID,
+        B3_SAMPLED,
+        B3_FLAGS,
+    ]
+    .map(String::from)
+});
+
+/// Header layout used when injecting B3 context.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum B3Encoding {
+    SingleHeader,
+    MultipleHeaders,
+}
+
+/// Injects B3 in the configured encoding and extracts either encoding,
+/// preferring the single `b3` header when both are present.
+#[derive(Clone, Debug)]
+pub struct B3Propagator {
+    encoding: B3Encoding,
+}
+
+impl B3Propagator {
+    pub fn single_header() -> Self {
+        Self {
+            encoding: B3Encoding::SingleHeader,
+        }
+    }
+
+    pub fn multiple_headers() -> Self {
+        Self {
+            encoding: B3Encoding::MultipleHeaders,
+        }
+    }
+
+    fn extract_single(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        let header = extractor.get(B3_SINGLE)?.trim();
+        let mut parts = header.split('-');
+        let trace_id = parse_trace_id(parts.next()?)?;
+        let span_id = parse_span_id(parts.next()?)?;
+        let flags = match parts.next() {
+            Some(sampled) => parse_sampled(sampled)?,
+            None => TraceFlags::SAMPLED,
+        };
+        // A fourth part is the parent span id, which OTel does not model.
+        Some(remote_context(trace_id, span_id, flags))
+    }
+
+    fn extract_multi(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        let trace_id = parse_trace_id(extractor.get(B3_TRACE_ID)?.trim())?;
+        let span_id = parse_span_id(extractor.get(B3_SPAN_ID)?.trim())?;
+        let flags = if extractor.get(B3_FLAGS).map(str::trim) == Some("1") {
+            TraceFlags::SAMPLED
+        } else {
+            match extractor.get(B3_SAMPLED) {
+                Some(sampled) => parse_sampled(sampled.trim())?,
+                None => TraceFlags::SAMPLED,
+            }
+        };
+        Some(remote_context(trace_id, span_id, flags))
+    }
+}
+
+impl TextMapPropagator for B3Propagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+
+        let sampled = if span_context.is_sampled() { "1" } else { "0" };
+        match self.encoding {
+            B3Encoding::SingleHeader => injector.set(
+                B3_SINGLE,
+                format!(
+                    "{}-{}-{sampled}",
+                    span_context.trace_id(),
+                    span_context.span_id()
+                ),
+            ),
+            B3Encoding::MultipleHeaders => {
+                injector.set(B3_TRACE_ID, span_context.trace_id().to_string());
+                injector.set(B3_SPAN_ID, span_context.span_id().to_string());
+                injector.set(B3_SAMPLED, sampled.to_string());
+            }
+        }
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        self.extract_single(extractor)
+            .or_else(|| self.extract_multi(extractor))
+            .map(|span_context| cx.with_remote_span_context(span_context))
+            .unwrap_or_else(|| cx.clone())
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        match self.encoding {
+            B3Encoding::SingleHeader => FieldIter::new(SINGLE_FIELDS.as_slice()),
+            B3Encoding::MultipleHeaders => FieldIter::new(MULTI_FIELDS.as_slice()),
+        }
+    }
+}
+
+/// 64-bit B3 trace ids are left-padded to 128 bits.
+fn parse_trace_id(value: &str) -> Option<TraceId> {
+    if !matches!(value.len(), 16 | 32) {
+        return None;
+    }
+    TraceId::from_hex(value)
+        .ok()
+        .filter(|id| *id != TraceId::INVALID)
+}
+
+fn parse_span_id(value: &str) -> Option<SpanId> {
+    if value.len() != 16 {
+        return None;
+    }
+    SpanId::from_hex(value)
+        .ok()
+        .filter(|id| *id != SpanId::INVALID)
+}
+
+/// `d` (debug) implies sampled.
+fn parse_sampled(value: &str) -> Option<TraceFlags> {
+    match value {
+        "1" | "d" | "true" => Some(TraceFlags::SAMPLED),
+        "0" | "false" => Some(TraceFlags::default()),
+        _ => None,
+    }
+}
+
+fn remote_context(trace_id: TraceId, span_id: SpanId, flags: TraceFlags) -> SpanContext {
+    SpanContext::new(trace_id, span_id, flags, true, TraceState::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn sampled_context() -> Context {
+        Context::new().with_remote_span_context(remote_context(
+            TraceId::from_hex(TRACE_ID).unwrap(),
+            SpanId::from_hex(SPAN_ID).unwrap(),
+            TraceFlags::SAMPLED,
+        ))
+    }
+
+    fn round_trip(propagator: &B3Propagator) -> (HashMap<String, String>, SpanContext) {
+        let mut headers = HashMap::new();
+        propagator.inject_context(&sampled_context(), &mut headers);
+        let cx = propagator.extract_with_context(&Context::new(), &headers);
+        (headers, cx.span().span_context().clone())
+    }
+
+    #[test]
+    fn single_header_round_trip() {
+        let (headers, extracted) = round_trip(&B3Propagator::single_header());
+        assert_eq!(headers["b3"], format!("{TRACE_ID}-{SPAN_ID}-1"));
+        assert_eq!(extracted.trace_id().to_string(), TRACE_ID);
+        assert_eq!(extracted.span_id().to_string(), SPAN_ID);
+        assert!(extracted.is_sampled() && extracted.is_remote());
+    }
+
+    #[test]
+    fn multi_header_round_trip() {
+        let (headers, extracted) = round_trip(&B3Propagator::multiple_headers());
+        assert_eq!(headers["x-b3-traceid"], TRACE_ID);
+        assert_eq!(headers["x-b3-sampled"], "1");
+        assert_eq!(extracted.span_id().to_string(), SPAN_ID);
+        assert!(extracted.is_sampled());
+    }
+
+    #[test]
+    fn extracts_short_ids_debug_and_deny() {
+        let propagator = B3Propagator::single_header();
+        let headers = HashMap::from([(
+            "b3".to_string(),
+            format!("a3ce929d0e0e4736-{SPAN_ID}-d-05e3ac9a4f6e3b90"),
+        )]);
+        let cx = propagator.extract_with_context(&Context::new(), &headers);
+        let extracted = cx.span().span_context().clone();
+        assert_eq!(
+            extracted.trace_id().to_string(),
+            "0000000000000000a3ce929d0e0e4736"
+        );
+        assert!(extracted.is_sampled());
+
+        let headers = HashMap::from([
+            ("x-b3-traceid".to_string(), TRACE_ID.to_string()),
+            ("x-b3-spanid".to_string(), SPAN_ID.to_string()),
+            ("x-b3-sampled".to_string(), "0".to_string()),
+        ]);
+        let cx = propagator.extract_with_context(&Context::new(), &headers);
+        assert!(cx.span().span_context().is_valid());
+        assert!(!cx.span().span_context().is_sampled());
+
+        let headers = HashMap::from([("b3".to_string(), "garbage".to_string())]);
+        let cx = propagator.extract_with_context(&Context::new(), &headers);
+        assert!(!cx.span().span_context().is_valid());
+    }
+}
@@ -0,0 +1,267 @@
+//! `TelemetryCtx` carried as `gt.*` entries of the W3C `baggage` header, or
+//! under whichever prefix [`crate::set_key_prefix`] configures.
+
+use std::fmt::Write as _;
+
+use crate::context::{TelemetryCtx, context_keys};
+
+/// W3C baggage limits: total header size, members per header and bytes per member.
+const MAX_BYTES: usize = 8192;
+const MAX_ENTRIES: usize = 180;
+const MAX_ENTRY_BYTES: usize = 4096;
+
+/// Which context keys may cross a trust boundary, and how much baggage to emit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaggagePolicy {
+    /// Full keys such as `gt.tenant`; a trailing `*` matches a prefix.
+    /// Defaults to `tenant`, `team`, `flow`, `node` and `run_id` under the
+    /// process-wide key prefix.
+    pub allowlist: Vec<String>,
+    pub max_bytes: usize,
+    pub max_entries: usize,
+    pub max_entry_bytes: usize,
+    /// Also read and write the legacy `x-tenant`/`x-team`/`x-flow`/`x-run-id`
+    /// headers, for the keys the allowlist lets through.
+    pub legacy_headers: bool,
+}
+
+impl Default for BaggagePolicy {
+    fn default() -> Self {
+        let keys = context_keys();
+        Self {
+            allowlist: ["tenant", "team", "flow", "node", "run_id"]
+                .map(|name| keys.key(name))
+                .to_vec(),
+            max_bytes: MAX_BYTES,
+            max_entries: MAX_ENTRIES,
+            max_entry_bytes: MAX_ENTRY_BYTES,
+            legacy_headers: false,
+        }
+    }
+}
+
+impl BaggagePolicy {
+    /// Default policy with the allowlist taken from `TELEMETRY_BAGGAGE_KEYS`
+    /// (comma-separated keys, `*` for all) and legacy headers from
+    /// `TELEMETRY_LEGACY_HEADERS=1`.
+    pub fn from_env() -> Self {
+        let mut policy = Self::default();
+        if let Ok(keys) = std::env::var("TELEMETRY_BAGGAGE_KEYS") {
+            policy.allowlist = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect();
+        }
+        policy.legacy_headers = std::env::var("TELEMETRY_LEGACY_HEADERS").as_deref() == Ok("1");
+        policy
+    }
+
+    pub fn allows(&self, key: &str) -> bool {
+        self.allowlist
+            .iter()
+            .any(|allowed| match allowed.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => allowed == key,
+            })
+    }
+
+    /// Merge the allowed entries of `ctx` into an `existing` baggage header,
+    /// replacing entries under the key prefix already present. Returns `None`
+    /// when the result would be empty.
+    pub fn encode(&self, ctx: &TelemetryCtx, existing: Option<&str>) -> Option<String> {
+        let keys = context_keys();
+        let mut members: Vec<String> = existing
+            .into_iter()
+            .flat_map(|header| header.split(','))
+            .map(str::trim)
+            .filter(|member| !member.is_empty() && keys.name(member_key(member)).is_none())
+            .map(String::from)
+            .collect();
+        let mut size = joined_len(&members);
+
+        for (key, value) in ctx.kv() {
+            if !self.allows(&key) || !is_token(&key) {
+                continue;
+            }
+            let member = format!("{key}={}", percent_encode(&value.to_string()));
+            let added = member.len() + usize::from(!members.is_empty());
+            if members.len() >= self.max_entries
+                || member.len() > self.max_entry_bytes
+                || size + added > self.max_bytes
+            {
+                continue;
+            }
+            size += added;
+            members.push(member);
+        }
+
+        (!members.is_empty()).then(|| members.join(","))
+    }
+
+    /// Allowed entries under the key prefix of a baggage header as a context.
+    /// Core keys map to their fields, any other key to a string attribute.
+    pub fn decode(&self, header: &str) -> TelemetryCtx {
+        let keys = context_keys();
+        let mut ctx = TelemetryCtx::default();
+        if header.len() > self.max_bytes {
+            return ctx;
+        }
+
+        for member in header.split(',').take(self.max_entries) {
+            let pair = member.split(';').next().unwrap_or_default();
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let Some(name) = keys.name(key) else {
+                continue;
+            };
+            if !self.allows(key) || member.len() > self.max_entry_bytes {
+                continue;
+            }
+
+            let value = percent_decode(value.trim());
+            match name {
+                "tenant" => ctx.tenant = value,
+                "session" => ctx.session = Some(value),
+                "flow" => ctx.flow = Some(value),
+                "node" => ctx.node = Some(value),
+                "provider" => ctx.provider = Some(value),
+                _ => ctx.insert_attr(name, value),
+            }
+        }
+        ctx
+    }
+}
+
+fn member_key(member: &str) -> &str {
+    member.split(['=', ';']).next().unwrap_or_default().trim()
+}
+
+fn joined_len(members: &[String]) -> usize {
+    members.iter().map(String::len).sum::<usize>() + members.len().saturating_sub(1)
+}
+
+/// RFC 7230 token characters, as required for baggage keys.
+fn is_token(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Percent-encode every byte outside the W3C `baggage-octet` range, plus `%`.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if is_baggage_octet(byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+fn is_baggage_octet(byte: u8) -> bool {
+    matches!(
+        byte,
+        0x21 | 0x23..=0x24 | 0x26..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E
+    )
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> TelemetryCtx {
+        TelemetryCtx::new("acme corp")
+            .with_session("secret-session")
+            .with_flow("intake,v2")
+            .with_attr("team", "pay%ments")
+            .with_attr("run_id", 42)
+    }
+
+    #[test]
+    fn round_trips_allowed_keys_with_encoding() {
+        let policy = BaggagePolicy::default();
+        let header = policy.encode(&ctx(), None).unwrap();
+        assert_eq!(
+            header,
+            "gt.tenant=acme%20corp,gt.flow=intake%2Cv2,gt.team=pay%25ments,gt.run_id=42"
+        );
+
+        let decoded = policy.decode(&header);
+        assert_eq!(decoded.tenant, "acme corp");
+        assert_eq!(decoded.flow.as_deref(), Some("intake,v2"));
+        assert_eq!(decoded.session, None, "session is not allowlisted");
+        assert_eq!(
+            decoded.value("team").map(|v| v.to_string()).as_deref(),
+            Some("pay%ments")
+        );
+    }
+
+    #[test]
+    fn merges_with_foreign_entries_and_drops_untrusted_keys() {
+        let policy = BaggagePolicy {
+            allowlist: vec!["gt.tenant".into()],
+            ..BaggagePolicy::default()
+        };
+        let header = policy
+            .encode(&ctx(), Some("userId=alice;prop, gt.tenant=stale"))
+            .unwrap();
+        assert_eq!(header, "userId=alice;prop,gt.tenant=acme%20corp");
+
+        let decoded = policy.decode("gt.tenant=acme;meta=1, gt.flow=forged, other=x");
+        assert_eq!(decoded.tenant, "acme");
+        assert_eq!(decoded.flow, None);
+    }
+
+    #[test]
+    fn enforces_size_limits() {
+        let policy = BaggagePolicy {
+            allowlist: vec!["gt.*".into()],
+            max_entries: 3,
+            max_entry_bytes: 32,
+            ..BaggagePolicy::default()
+        };
+        let ctx = TelemetryCtx::new("acme")
+            .with_node("x".repeat(64))
+            .with_attr("a", "1")
+            .with_attr("b", "2")
+            .with_attr("c", "3");
+        let header = policy.encode(&ctx, None).unwrap();
+        assert_eq!(header, "gt.tenant=acme,gt.a=1,gt.b=2");
+
+        let policy = BaggagePolicy {
+            max_bytes: 16,
+            ..policy
+        };
+        assert_eq!(policy.encode(&ctx, None).unwrap(), "gt.tenant=acme");
+    }
+}
@@ -0,0 +1,173 @@
+//! [`Carrier`] implementations for common header types.
+//!
+//! Keys are matched case-insensitively and written lowercase, as HTTP and
+//! gRPC require. Values that are not valid header values are dropped.
+
+use std::collections::HashMap;
+
+use super::Carrier;
+
+/// Maps built elsewhere may hold mixed-case keys, so lookups scan rather
+/// than hash the lowercased key.
+impl Carrier for HashMap<String, String> {
+    fn set(&mut self, key: &str, value: String) {
+        self.retain(|name, _| !name.eq_ignore_ascii_case(key));
+        self.insert(key.to_ascii_lowercase(), value);
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.clone())
+    }
+
+    fn keys(&self) -> Vec<String> {
+        HashMap::keys(self).cloned().collect()
+    }
+}
+
+/// Message headers as raw bytes, the shape used by NATS and Kafka clients.
+/// Existing entries for a key are replaced; non-UTF-8 values are ignored.
+impl Carrier for Vec<(String, Vec<u8>)> {
+    fn set(&mut self, key: &str, value: String) {
+        self.retain(|(name, _)| !name.eq_ignore_ascii_case(key));
+        self.push((key.to_ascii_lowercase(), value.into_bytes()));
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .and_then(|(_, value)| String::from_utf8(value.clone()).ok())
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+#[cfg(feature = "http")]
+impl Carrier for http::HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        let (Ok(name), Ok(value)) = (
+            http::HeaderName::from_bytes(key.to_ascii_lowercase().as_bytes()),
+            http::HeaderValue::from_str(&value),
+        ) else {
+            return;
+        };
+        self.insert(name, value);
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        http::HeaderMap::get(self, key.to_ascii_lowercase())
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        http::HeaderMap::keys(self)
+            .map(|name| name.as_str().to_string())
+            .collect()
+    }
+}
+
+/// ASCII metadata only; `-bin` entries are skipped.
+#[cfg(feature = "tonic")]
+impl Carrier for tonic::metadata::MetadataMap {
+    fn set(&mut self, key: &str, value: String) {
+        let (Ok(name), Ok(value)) = (
+            tonic::metadata::MetadataKey::from_bytes(key.to_ascii_lowercase().as_bytes()),
+            tonic::metadata::MetadataValue::try_from(value),
+        ) else {
+            return;
+        };
+        self.insert(name, value);
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        tonic::metadata::MetadataMap::get(self, key.to_ascii_lowercase())
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    }
+
+    fn keys(&self) -> Vec<String> {
+        tonic::metadata::MetadataMap::keys(self)
+            .filter_map(|key| match key {
+                tonic::metadata::KeyRef::Ascii(name) => Some(name.as_str().to_string()),
+                tonic::metadata::KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::propagation::{CarrierExtractor, extract_remote};
+    use opentelemetry::propagation::Extractor;
+
+    fn exercise(carrier: &mut impl Carrier) {
+        carrier.set("TraceParent", "00-abc-def-01".into());
+        carrier.set("baggage", "gt.tenant=acme".into());
+        carrier.set("traceparent", "00-123-456-01".into());
+
+        assert_eq!(carrier.get("TRACEPARENT").as_deref(), Some("00-123-456-01"));
+        assert_eq!(carrier.get("missing"), None);
+        let mut keys = carrier.keys();
+        keys.sort();
+        assert_eq!(keys, ["baggage", "traceparent"]);
+    }
+
+    #[test]
+    fn hash_map_and_byte_headers() {
+        exercise(&mut HashMap::<String, String>::new());
+
+        let mut headers: Vec<(String, Vec<u8>)> = vec![("x-bin".into(), vec![0xff])];
+        assert_eq!(
+            Carrier::get(&headers, "x-bin"),
+            None,
+            "non-UTF-8 values are ignored"
+        );
+        headers.clear();
+        exercise(&mut headers);
+    }
+
+    #[test]
+    fn mixed_case_hash_map_keys_are_extracted() {
+        let headers = HashMap::from([
+            (
+                "Traceparent".to_string(),
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+            ),
+            ("Baggage".to_string(), "gt.tenant=acme".to_string()),
+        ]);
+
+        let extractor = CarrierExtractor::new(&headers);
+        assert_eq!(
+            extractor.get("traceparent"),
+            Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+        );
+        let ctx = extract_remote(&headers, &tracing::Span::none());
+        assert_eq!(ctx.tenant, "acme");
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn http_header_map() {
+        let mut headers = http::HeaderMap::new();
+        exercise(&mut headers);
+        headers.set("bad", "line\nbreak".into());
+        assert_eq!(Carrier::get(&headers, "bad"), None);
+    }
+
+    #[cfg(feature = "tonic")]
+    #[test]
+    fn tonic_metadata_map() {
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        metadata.insert_bin(
+            "trace-bin",
+            tonic::metadata::MetadataValue::from_bytes(b"\x00"),
+        );
+        assert!(Carrier::keys(&metadata).is_empty());
+        exercise(&mut metadata);
+    }
+}
@@ -0,0 +1,154 @@
+//! Jaeger `uber-trace-id` propagation.
+
+use once_cell::sync::Lazy;
+use opentelemetry::{
+    Context,
+    propagation::{Extractor, Injector, TextMapPropagator, text_map_propagator::FieldIter},
+    trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState},
+};
+
+const UBER_TRACE_ID: &str = "uber-trace-id";
+const FLAG_SAMPLED: u8 = 0x01;
+const FLAG_DEBUG: u8 = 0x02;
+
+static FIELDS: Lazy<[String; 1]> = Lazy::new(|| [UBER_TRACE_ID.to_string()]);
+
+/// Propagates `{trace-id}:{span-id}:{parent-span-id}:{flags}` in the
+/// `uber-trace-id` header. Jaeger baggage (`uberctx-*`) is not carried; use
+/// the W3C baggage propagator alongside it.
+#[derive(Clone, Debug, Default)]
+pub struct JaegerPropagator;
+
+impl JaegerPropagator {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn extract_span_context(&self, extractor: &dyn Extractor) -> Option<SpanContext> {
+        let header = extractor.get(UBER_TRACE_ID)?.trim();
+        // Some clients URL-encode the separators.
+        let header = header.replace("%3A", ":").replace("%3a", ":");
+        let mut parts = header.split(':');
+        let trace_id = parts.next().and_then(|v| parse_id(v, 32))?;
+        let span_id = parts.next().and_then(|v| parse_id(v, 16))?;
+        let _parent = parts.next()?;
+        let flags = u8::from_str_radix(parts.next()?, 16).ok()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let trace_id = TraceId::from_hex(&trace_id).ok()?;
+        let span_id = SpanId::from_hex(&span_id).ok()?;
+        if trace_id == TraceId::INVALID || span_id == SpanId::INVALID {
+            return None;
+        }
+
+        let flags = if flags & (FLAG_SAMPLED | FLAG_DEBUG) != 0 {
+            TraceFlags::SAMPLED
+        } else {
+            TraceFlags::default()
+        };
+        Some(SpanContext::new(
+            trace_id,
+            span_id,
+            flags,
+            true,
+            TraceState::default(),
+        ))
+    }
+}
+
+impl TextMapPropagator for JaegerPropagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        let span = cx.span();
+        let span_context = span.span_context();
+        if !span_context.is_valid() {
+            return;
+        }
+
+        let flags = if span_context.is_sampled() {
+            FLAG_SAMPLED
+        } else {
+            0
+        };
+        injector.set(
+            UBER_TRACE_ID,
+            format!(
+                "{}:{}:0:{flags:x}",
+                span_context.trace_id(),
+                span_context.span_id()
+            ),
+        );
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        self.extract_span_context(extractor)
+            .map(|span_context| cx.with_remote_span_context(span_context))
+            .unwrap_or_else(|| cx.clone())
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        FieldIter::new(FIELDS.as_slice())
+    }
+}
+
+/// Jaeger drops leading zeros, so ids are left-padded to `width` hex digits.
+fn parse_id(value: &str, width: usize) -> Option<String> {
+    if value.is_empty() || value.len() > width || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("{value:0>width$}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn round_trip() {
+        let cx = Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from_hex(TRACE_ID).unwrap(),
+            SpanId::from_hex(SPAN_ID).unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ));
+
+        let mut headers = HashMap::new();
+        JaegerPropagator::new().inject_context(&cx, &mut headers);
+        assert_eq!(
+            headers["uber-trace-id"],
+            format!("{TRACE_ID}:{SPAN_ID}:0:1")
+        );
+
+        let extracted = JaegerPropagator::new().extract_with_context(&Context::new(), &headers);
+        let span_context = extracted.span().span_context().clone();
+        assert_eq!(span_context.trace_id().to_string(), TRACE_ID);
+        assert_eq!(span_context.span_id().to_string(), SPAN_ID);
+        assert!(span_context.is_sampled() && span_context.is_remote());
+    }
+
+    #[test]
+    fn extracts_short_and_encoded_ids() {
+        let headers = HashMap::from([(
+            "uber-trace-id".to_string(),
+            "a3ce929d0e0e4736%3Af067aa0ba902b7%3A0%3A0".to_string(),
+        )]);
+        let cx = JaegerPropagator::new().extract_with_context(&Context::new(), &headers);
+        let span_context = cx.span().span_context().clone();
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "0000000000000000a3ce929d0e0e4736"
+        );
+        assert_eq!(span_context.span_id().to_string(), SPAN_ID);
+        assert!(!span_context.is_sampled());
+
+        let headers = HashMap::from([("uber-trace-id".to_string(), "0:0:0:1".to_string())]);
+        let cx = JaegerPropagator::new().extract_with_context(&Context::new(), &headers);
+        assert!(!cx.span().span_context().is_valid());
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use anyhow::{Result, anyhow};
+use opentelemetry::global;
+use opentelemetry::propagation::{
+    Extractor, Injector, TextMapCompositePropagator, TextMapPropagator,
+};
+use opentelemetry_sdk::propagation::{BaggagePropagator, TraceContextPropagator};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use once_cell::sync::Lazy;
+
+use crate::context::{CloudCtx, TelemetryCtx, context_keys, context_snapshot, set_context};
+
+mod b3;
+mod baggage;
+mod carriers;
+mod jaeger;
+
+pub use b3::{B3Encoding, B3Propagator};
+pub use baggage::BaggagePolicy;
+pub use jaeger::JaegerPropagator;
+
+/// Trace context formats understood by [`inject_carrier`] and [`extract_carrier`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagator {
+    TraceContext,
+    Baggage,
+    B3,
+    B3Multi,
+    Jaeger,
+}
+
+impl Propagator {
+    /// The OpenTelemetry default, `tracecontext,baggage`.
+    pub const DEFAULT: [Propagator; 2] = [Propagator::TraceContext, Propagator::Baggage];
+
+    /// Parse an `OTEL_PROPAGATORS` value; `none` disables propagation.
+    pub fn parse_list(value: &str) -> Result<Vec<Propagator>> {
+        let mut list = Vec::new();
+        for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            if name.eq_ignore_ascii_case("none") {
+                return Ok(Vec::new());
+            }
+            let propagator = name.parse()?;
+            if !list.contains(&propagator) {
+                list.push(propagator);
+            }
+        }
+        Ok(list)
+    }
+
+    /// Read `OTEL_PROPAGATORS`, falling back to [`Propagator::DEFAULT`].
+    pub fn from_env() -> Result<Vec<Propagator>> {
+        match std::env::var("OTEL_PROPAGATORS") {
+            Ok(value) if !value.trim().is_empty() => Self::parse_list(&value),
+            _ => Ok(Self::DEFAULT.to_vec()),
+        }
+    }
+
+    fn build(self) -> Box<dyn TextMapPropagator + Send + Sync> {
+        match self {
+            Propagator::TraceContext => Box::new(TraceContextPropagator::new()),
+            Propagator::Baggage => Box::new(BaggagePropagator::new()),
+            Propagator::B3 => Box::new(B3Propagator::single_header()),
+            Propagator::B3Multi => Box::new(B3Propagator::multiple_headers()),
+            Propagator::Jaeger => Box::new(JaegerPropagator::new()),
+        }
+    }
+}
+
+impl FromStr for Propagator {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "tracecontext" => Ok(Propagator::TraceContext),
+            "baggage" => Ok(Propagator::Baggage),
+            "b3" => Ok(Propagator::B3),
+            "b3multi" => Ok(Propagator::B3Multi),
+            "jaeger" => Ok(Propagator::Jaeger),
+            other => Err(anyhow!(
+                "unsupported propagator '{other}', expected tracecontext, baggage, b3, b3multi or jaeger"
+            )),
+        }
+    }
+}
+
+/// Combine `propagators`; injection writes every format and extraction
+/// lets later formats override earlier ones.
+pub fn composite(propagators: &[Propagator]) -> TextMapCompositePropagator {
+    TextMapCompositePropagator::new(propagators.iter().map(|p| p.build()).collect())
+}
+
+const BAGGAGE_HEADER: &str = "baggage";
+
+static BAGGAGE_POLICY: Lazy<RwLock<BaggagePolicy>> =
+    Lazy::new(|| RwLock::new(BaggagePolicy::from_env()));
+
+/// Replace the policy used to carry `TelemetryCtx` in the `baggage` header.
+pub fn set_baggage_policy(policy: BaggagePolicy) {
+    *BAGGAGE_POLICY.write().unwrap_or_else(|e| e.into_inner()) = policy;
+}
+
+fn baggage_policy() -> BaggagePolicy {
+    BAGGAGE_POLICY
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Legacy context headers, by unprefixed name. Read and written only when
+/// [`BaggagePolicy::legacy_headers`] is set, and only for allowed keys.
+const CONTEXT_HEADERS: [(&str, &str); 4] = [
+    ("tenant", "x-tenant"),
+    ("team", "x-team"),
+    ("flow", "x-flow"),
+    ("run_id", "x-run-id"),
+];
+
+/// Minimal header carrier abstraction for propagation.
+///
+/// Implemented for `HashMap<String, String>`, byte-valued header lists
+/// (`Vec<(String, Vec<u8>)>`, as used by NATS and Kafka), `http::HeaderMap`
+/// and, with the `tonic` feature, `tonic::metadata::MetadataMap`.
+pub trait Carrier {
+    fn set(&mut self, key: &str, value: String);
+    fn get(&self, key: &str) -> Option<String>;
+    /// Every key present, so propagators can scan for prefixed entries.
+    fn keys(&self) -> Vec<String>;
+}
+
+/// Inject the current span context, and the current `TelemetryCtx` as `gt.*`
+/// baggage entries, into the carrier.
+pub fn inject_carrier(headers: &mut impl Carrier) {
+    global::get_text_map_propagator(|propagator| {
+        let mut injector = CarrierInjector { carrier: headers };
+        propagator.inject_context(&Span::current().context(), &mut injector);
+    });
+
+    let Some(ctx) = context_snapshot() else {
+        return;
+    };
+    let policy = baggage_policy();
+    let existing = headers.get(BAGGAGE_HEADER);
+    if let Some(baggage) = policy.encode(&ctx, existing.as_deref()) {
+        headers.set(BAGGAGE_HEADER, baggage);
+    }
+
+    if policy.legacy_headers {
+        let keys = context_keys();
+        for (name, header) in CONTEXT_HEADERS {
+            if let Some(value) = ctx.value(name)
+                && policy.allows(&keys.key(name))
+            {
+                headers.set(header, value.to_string());
+            }
+        }
+    }
+}
+
+/// Extract span context and cloud metadata from the carrier into the current span.
+pub fn extract_carrier(headers: &impl Carrier) {
+    let span = Span::current();
+    extract_carrier_into_span(headers, &span);
+}
+
+/// Extract span context and cloud metadata from the carrier into the provided span.
+///
+/// The context becomes current as with [`set_context`]: on the task inside
+/// [`crate::with_task_local`], on the thread outside any runtime, and
+/// nowhere otherwise; middleware should scope it instead.
+pub fn extract_carrier_into_span(headers: &impl Carrier, span: &Span) {
+    set_context(extract_remote(headers, span));
+}
+
+/// Parent `span` on the remote span context and return the remote
+/// `TelemetryCtx`, leaving the current context untouched.
+pub(crate) fn extract_remote(headers: &impl Carrier, span: &Span) -> TelemetryCtx {
+    let extractor = CarrierExtractor::new(headers);
+    let parent_ctx = global::get_text_map_propagator(|propagator| propagator.extract(&extractor));
+
+    if let Err(err) = span.set_parent(parent_ctx) {
+        tracing::debug!(
+            target: "greentic.telemetry",
+            error = %err,
+            "extract_carrier could not apply remote parent context"
+        );
+    }
+
+    extract_context(headers, &baggage_policy())
+}
+
+/// Context from the allowed baggage entries under the key prefix. When the policy enables
+/// legacy headers, the allowed `x-*` ones fill any field the baggage does
+/// not carry.
+fn extract_context(headers: &impl Carrier, policy: &BaggagePolicy) -> TelemetryCtx {
+    let keys = context_keys();
+    let [tenant, team, flow, run_id] = CONTEXT_HEADERS.map(|(name, header)| {
+        let trusted = policy.legacy_headers && policy.allows(&keys.key(name));
+        trusted.then(|| headers.get(header)).flatten()
+    });
+    let legacy = TelemetryCtx::from(CloudCtx {
+        tenant: tenant.as_deref(),
+        team: team.as_deref(),
+        flow: flow.as_deref(),
+        run_id: run_id.as_deref(),
+    });
+
+    match headers.get(BAGGAGE_HEADER) {
+        Some(baggage) => legacy.overlay(&policy.decode(&baggage)),
+        None => legacy,
+    }
+}
+
+struct CarrierInjector<'a, C> {
+    carrier: &'a mut C,
+}
+
+impl<'a, C: Carrier> Injector for CarrierInjector<'a, C> {
+    fn set(&mut self, key: &str, value: String) {
+        self.carrier.set(key, value);
+    }
+}
+
+/// Lowercased snapshot of a carrier, since [`Extractor`] hands out borrowed values.
+struct CarrierExtractor {
+    headers: HashMap<String, String>,
+}
+
+impl CarrierExtractor {
+    fn new(carrier: &impl Carrier) -> Self {
+        let headers = carrier
+            .keys()
+            .into_iter()
+            .filter_map(|key| {
+                let value = carrier.get(&key)?;
+                Some((key.to_ascii_lowercase(), value))
+            })
+            .collect();
+        Self { headers }
+    }
+}
+
+impl Extractor for CarrierExtractor {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.headers
+            .get(&key.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.headers.keys().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasklocal::with_task_local;
+    use opentelemetry::trace::{TraceContextExt, TraceId, TracerProvider as _};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    #[derive(Default)]
+    struct MockCarrier {
+        headers: HashMap<String, String>,
+    }
+
+    impl Carrier for MockCarrier {
+        fn set(&mut self, key: &str, value: String) {
+            self.headers.insert(key.to_string(), value);
+        }
+
+        fn get(&self, key: &str) -> Option<String> {
+            self.headers.get(key).cloned()
+        }
+
+        fn keys(&self) -> Vec<String> {
+            self.headers.keys().cloned().collect()
+        }
+    }
+
+    const ALL: [Propagator; 5] = [
+        Propagator::TraceContext,
+        Propagator::Baggage,
+        Propagator::B3,
+        Propagator::B3Multi,
+        Propagator::Jaeger,
+    ];
+
+    /// Tests share the global propagator, so they all install the same one.
+    fn install_propagators() -> impl tracing::Subscriber + Send + Sync {
+        global::set_text_map_propagator(composite(&ALL));
+        let provider = SdkTracerProvider::builder().build();
+        Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("propagation-test")))
+    }
+
+    #[test]
+    fn parses_otel_propagators() {
+        assert_eq!(
+            Propagator::parse_list("tracecontext, B3Multi,jaeger,b3multi").unwrap(),
+            [
+                Propagator::TraceContext,
+                Propagator::B3Multi,
+                Propagator::Jaeger
+            ]
+        );
+        assert!(Propagator::parse_list("none").unwrap().is_empty());
+        assert!(Propagator::parse_list("xray").is_err());
+    }
+
+    #[test]
+    fn every_format_round_trips_through_carriers() {
+        let _default = tracing::subscriber::set_default(install_propagators());
+
+        let parent = tracing::info_span!("parent");
+        let trace_id = parent.context().span().span_context().trace_id();
+        let mut carrier = MockCarrier::default();
+        {
+            let _entered = parent.enter();
+            inject_carrier(&mut carrier);
+        }
+
+        let formats: [&[&str]; 4] = [
+            &["traceparent"],
+            &["b3"],
+            &["x-b3-traceid", "x-b3-spanid", "x-b3-sampled"],
+            &["uber-trace-id"],
+        ];
+        for headers in formats {
+            let single = MockCarrier {
+                headers: headers
+                    .iter()
+                    .map(|h| (h.to_string(), carrier.headers[*h].clone()))
+                    .collect(),
+            };
+            let child = tracing::info_span!("child");
+            extract_carrier_into_span(&single, &child);
+            let extracted: TraceId = child.context().span().span_context().trace_id();
+            assert_eq!(extracted, trace_id, "{headers:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trip_trace_and_context() {
+        let subscriber = install_propagators();
+
+        with_task_local(async {
+            let _default = tracing::subscriber::set_default(subscriber);
+            set_context(CloudCtx {
+                tenant: Some("tenant-123"),
+                team: Some("team-xyz"),
+                flow: Some("flow-abc"),
+                run_id: Some("run-0001"),
+            });
+
+            let parent_span = tracing::info_span!("parent");
+            let mut carrier = MockCarrier::default();
+            {
+                let _guard = parent_span.enter();
+                inject_carrier(&mut carrier);
+            }
+            let parent_trace_id = parent_span.context().span().span_context().trace_id();
+
+            assert!(carrier.headers.contains_key("traceparent"));
+            assert_eq!(
+                carrier.headers.get("baggage").map(String::as_str),
+                Some("gt.tenant=tenant-123,gt.flow=flow-abc,gt.team=team-xyz,gt.run_id=run-0001")
+            );
+            assert!(!carrier.headers.contains_key("x-tenant"));
+
+            // Clear local context before extraction to ensure values come from headers.
+            set_context(CloudCtx::empty());
+            assert!(context_snapshot().is_none());
+
+            let child_span = tracing::info_span!("child");
+            extract_carrier_into_span(&carrier, &child_span);
+            let child_trace_id = child_span.context().span().span_context().trace_id();
+            assert_eq!(child_trace_id, parent_trace_id);
+
+            let ctx = context_snapshot().expect("context restored from headers");
+            assert_eq!(ctx.tenant, "tenant-123");
+            assert_eq!(ctx.flow.as_deref(), Some("flow-abc"));
+            assert_eq!(
+                ctx.value("team").map(|v| v.to_string()).as_deref(),
+                Some("team-xyz")
+            );
+            assert_eq!(
+                ctx.value("run_id").map(|v| v.to_string()).as_deref(),
+                Some("run-0001")
+            );
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn http_headers_round_trip_through_message_headers() {
+        let subscriber = install_propagators();
+
+        with_task_local(async {
+            let _default = tracing::subscriber::set_default(subscriber);
+            set_context(TelemetryCtx::new("acme").with_flow("intake"));
+
+            let parent = tracing::info_span!("parent");
+            let mut headers = http::HeaderMap::new();
+            {
+                let _entered = parent.enter();
+                inject_carrier(&mut headers);
+            }
+            let message: Vec<(String, Vec<u8>)> = headers
+                .iter()
+                .map(|(name, value)| (name.as_str().to_uppercase(), value.as_bytes().to_vec()))
+                .collect();
+
+            set_context(CloudCtx::empty());
+            let child = tracing::info_span!("child");
+            extract_carrier_into_span(&message, &child);
+            assert_eq!(
+                child.context().span().span_context().trace_id(),
+                parent.context().span().span_context().trace_id()
+            );
+            let ctx = context_snapshot().expect("context from message headers");
+            assert_eq!(ctx.tenant, "acme");
+            assert_eq!(ctx.flow.as_deref(), Some("intake"));
+        })
+        .await;
+    }
+
+    #[test]
+    fn legacy_headers_fill_gaps_in_baggage_when_enabled() {
+        let carrier = MockCarrier {
+            headers: HashMap::from([
+                ("x-tenant".to_string(), "legacy".to_string()),
+                ("x-run-id".to_string(), "run-9".to_string()),
+                (
+                    "baggage".to_string(),
+                    "gt.tenant=acme,gt.flow=intake".to_string(),
+                ),
+            ]),
+        };
+        let policy = BaggagePolicy {
+            legacy_headers: true,
+            ..BaggagePolicy::default()
+        };
+
+        let ctx = extract_context(&carrier, &policy);
+        assert_eq!(ctx.tenant, "acme");
+        assert_eq!(ctx.flow.as_deref(), Some("intake"));
+        assert_eq!(
+            ctx.value("run_id").map(|v| v.to_string()).as_deref(),
+            Some("run-9")
+        );
+    }
+
+    #[test]
+    fn legacy_headers_cannot_forge_untrusted_keys() {
+        let carrier = MockCarrier {
+            headers: HashMap::from([
+                ("x-tenant".to_string(), "forged".to_string()),
+                ("x-flow".to_string(), "intake".to_string()),
+            ]),
+        };
+
+        let ctx = extract_context(&carrier, &BaggagePolicy::default());
+        assert_eq!(ctx, TelemetryCtx::default(), "legacy headers are off");
+
+        let policy = BaggagePolicy {
+            allowlist: vec!["gt.flow".to_string()],
+            legacy_headers: true,
+            ..BaggagePolicy::default()
+        };
+        let ctx = extract_context(&carrier, &policy);
+        assert_eq!(ctx.tenant, "");
+        assert_eq!(ctx.flow.as_deref(), Some("intake"));
+    }
+
+    fn tenant_carrier(tenant: &str) -> MockCarrier {
+        MockCarrier {
+            headers: HashMap::from([("baggage".to_string(), format!("gt.tenant={tenant}"))]),
+        }
+    }
+
+    #[test]
+    fn concurrent_extracts_on_threads_stay_on_their_thread() {
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(2));
+        let threads: Vec<_> = ["tenant-a", "tenant-b"]
+            .into_iter()
+            .map(|tenant| {
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    extract_carrier_into_span(&tenant_carrier(tenant), &Span::none());
+                    barrier.wait();
+                    context_snapshot().map(|ctx| ctx.tenant)
+                })
+            })
+            .collect();
+        let tenants: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert_eq!(
+            tenants,
+            [Some("tenant-a".to_string()), Some("tenant-b".to_string())]
+        );
+        assert!(context_snapshot().is_none(), "nothing process-wide");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn concurrent_extracts_outside_task_local_leak_nowhere() {
+        let tasks: Vec<_> = ["tenant-a", "tenant-b"]
+            .into_iter()
+            .map(|tenant| {
+                tokio::spawn(async move {
+                    extract_carrier_into_span(&tenant_carrier(tenant), &Span::none());
+                    tokio::task::yield_now().await;
+                    context_snapshot().map(|ctx| ctx.tenant)
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert_eq!(task.await.unwrap(), None);
+        }
+        assert!(context_snapshot().is_none());
+        let other = tokio::spawn(async { context_snapshot() }).await.unwrap();
+        assert!(other.is_none(), "{other:?}");
+    }
+}
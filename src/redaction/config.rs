@@ -0,0 +1,147 @@
+//! Validation of the redaction configuration, and a self-test of the
+//! built-in patterns run at startup.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use super::{DEFAULT_PATTERNS, RedactionMode, RedactionPolicies, RedactionPolicy};
+
+/// Why the `PII_*` configuration, or the policy file, cannot be trusted.
+#[derive(Debug, Error)]
+pub enum RedactionConfigError {
+    #[error("unknown {var} value '{value}', expected off, strict or allowlist")]
+    UnknownMode { var: &'static str, value: String },
+    #[error("invalid regex '{pattern}' in {var}: {source}")]
+    InvalidRegex {
+        var: &'static str,
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+    #[error("invalid {var} entry '{entry}': {reason}")]
+    InvalidEntry {
+        var: &'static str,
+        entry: String,
+        reason: String,
+    },
+    #[error("invalid redaction policy file {}: {reason}", path.display())]
+    PolicyFile { path: PathBuf, reason: String },
+    #[error(
+        "built-in pattern '{pattern}' {} sample {sample:?}",
+        if *should_match { "missed" } else { "wrongly matched" }
+    )]
+    SelfTest {
+        pattern: &'static str,
+        sample: &'static str,
+        should_match: bool,
+    },
+}
+
+/// Known samples for each built-in pattern, and whether it must match them.
+const SAMPLES: &[(&str, &str, bool)] = &[
+    ("email", "alice@example.com", true),
+    ("email", "Bob.Smith+tag@mail.example.co.uk", true),
+    ("email", "alice at example dot com", false),
+    ("bearer", "Authorization: Bearer abc.DEF-123", true),
+    ("bearer", "the bearer", false),
+    ("api_key", "api_key=sk_live_123", true),
+    ("api_key", "X-Api-Key: abc123", true),
+    ("api_key", "token: eyJhbGciOi", true),
+    ("api_key", "tokens remaining 3", false),
+    ("phone", "+1 555-010-9901", true),
+    ("phone", "+441234567890", true),
+    ("phone", "build 42 took 7s", false),
+];
+
+/// Check every built-in pattern against known samples, and that a strict
+/// policy rewrites each expected match.
+pub fn self_test() -> Result<(), RedactionConfigError> {
+    let strict = RedactionPolicy::new(RedactionMode::Strict);
+    for &(pattern, sample, should_match) in SAMPLES {
+        let regex = DEFAULT_PATTERNS
+            .iter()
+            .find(|(builtin, _)| *builtin == pattern)
+            .map(|(_, regex)| regex)
+            .expect("sample for a built-in pattern");
+        let redacted = strict.redact("self_test", sample);
+        let masked = redacted.as_deref() != Some(sample);
+        if regex.is_match(sample) != should_match || (should_match && !masked) {
+            return Err(RedactionConfigError::SelfTest {
+                pattern,
+                sample,
+                should_match,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check the `PII_*` variables and `PII_POLICY_FILE` without installing
+/// anything, then run [`self_test`].
+pub fn validate_redaction_config() -> Result<(), RedactionConfigError> {
+    RedactionPolicies::try_from_env()?;
+    self_test()
+}
+
+/// Whether `PII_STRICT_CONFIG` asks for invalid configuration to fail
+/// startup rather than be skipped with a warning.
+pub fn strict_from_env() -> bool {
+    std::env::var("PII_STRICT_CONFIG")
+        .map(|value| {
+            matches!(
+                value.trim().to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// Run `load`, failing with the first problem it reports.
+pub(crate) fn first_error<T>(
+    load: impl FnOnce(&mut dyn FnMut(RedactionConfigError)) -> T,
+) -> Result<T, RedactionConfigError> {
+    let mut first = None;
+    let value = load(&mut |err| {
+        first.get_or_insert(err);
+    });
+    match first {
+        Some(err) => Err(err),
+        None => Ok(value),
+    }
+}
+
+pub(crate) fn warn(err: RedactionConfigError) {
+    tracing::warn!("{err}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_patterns_pass_self_test() {
+        self_test().unwrap();
+    }
+
+    #[test]
+    fn first_error_is_returned() {
+        let result = first_error(|report| {
+            report(RedactionConfigError::UnknownMode {
+                var: "PII_REDACTION_MODE",
+                value: "loose".into(),
+            });
+            report(RedactionConfigError::InvalidEntry {
+                var: "PII_FIELD_STRATEGIES",
+                entry: "x".into(),
+                reason: "expected name=strategy".into(),
+            });
+        });
+        let err = result.unwrap_err();
+        assert!(matches!(err, RedactionConfigError::UnknownMode { .. }));
+        assert_eq!(
+            err.to_string(),
+            "unknown PII_REDACTION_MODE value 'loose', expected off, strict or allowlist"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Redaction for the `tracing_subscriber::fmt` layers.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json::Value;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::field::{MakeVisitor, VisitFmt, VisitOutput};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::{RedactionPolicy, current_policy};
+
+/// Field formatter masking string and debug values before the wrapped text
+/// formatter (`DefaultFields`, `PrettyFields`) writes them.
+#[derive(Clone, Debug, Default)]
+pub struct RedactingFields<M> {
+    inner: M,
+}
+
+impl<M> RedactingFields<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner }
+    }
+}
+
+impl<T, M: MakeVisitor<T>> MakeVisitor<T> for RedactingFields<M> {
+    type Visitor = RedactingVisitor<M::Visitor>;
+
+    fn make_visitor(&self, target: T) -> Self::Visitor {
+        RedactingVisitor {
+            inner: self.inner.make_visitor(target),
+            policy: current_policy(),
+        }
+    }
+}
+
+/// Visitor produced by [`RedactingFields`], applying the current tenant's
+/// policy.
+#[derive(Debug)]
+pub struct RedactingVisitor<V> {
+    inner: V,
+    policy: Arc<RedactionPolicy>,
+}
+
+impl<V: Visit> Visit for RedactingVisitor<V> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if !self.policy.is_active() {
+            self.inner.record_str(field, value);
+        } else if let Some(masked) = redact_message(&self.policy, field.name(), value) {
+            self.inner.record_str(field, &masked);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.policy.is_active() {
+            self.inner.record_debug(field, value);
+        } else if let Some(masked) =
+            redact_message(&self.policy, field.name(), &format!("{value:?}"))
+        {
+            self.inner.record_debug(field, &format_args!("{masked}"));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.inner.record_i64(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.inner.record_u64(field, value);
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.inner.record_i128(field, value);
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.inner.record_u128(field, value);
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.inner.record_f64(field, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.inner.record_bool(field, value);
+    }
+}
+
+impl<V: VisitOutput<fmt::Result>> VisitOutput<fmt::Result> for RedactingVisitor<V> {
+    fn finish(self) -> fmt::Result {
+        self.inner.finish()
+    }
+}
+
+impl<V: VisitFmt> VisitFmt for RedactingVisitor<V> {
+    fn writer(&mut self) -> &mut dyn fmt::Write {
+        self.inner.writer()
+    }
+}
+
+/// Keys the JSON formatter writes itself rather than taking from fields.
+const JSON_METADATA: &[&str] = &[
+    "timestamp",
+    "level",
+    "target",
+    "filename",
+    "line_number",
+    "threadName",
+    "threadId",
+];
+
+/// Event formatter for JSON output. The JSON formatter records event fields
+/// itself, bypassing [`RedactingFields`], so each line is masked by key once
+/// formatted, keeping the formatter's key order; span fields and injected
+/// context are covered the same way.
+#[derive(Clone, Debug, Default)]
+pub struct RedactingFormat<E> {
+    inner: E,
+}
+
+impl<E> RedactingFormat<E> {
+    pub fn new(inner: E) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S, N, E> FormatEvent<S, N> for RedactingFormat<E>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    E: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let policy = current_policy();
+        if !policy.is_active() {
+            return self.inner.format_event(ctx, writer, event);
+        }
+
+        let mut line = String::new();
+        self.inner
+            .format_event(ctx, Writer::new(&mut line), event)?;
+        let Ok(mut json) = serde_json::from_str::<Value>(line.trim_end()) else {
+            return writer.write_str(&policy.redact_field("message", &line));
+        };
+        if let Value::Object(map) = &mut json {
+            map.retain(|key, value| {
+                JSON_METADATA.contains(&key.as_str()) || redact_json(&policy, key, value)
+            });
+        }
+        writeln!(writer, "{json}")
+    }
+}
+
+/// Mask every string in `value`, keyed by its nearest object key. Returns
+/// `false` when `value` is to be dropped from its container.
+fn redact_json(policy: &RedactionPolicy, key: &str, value: &mut Value) -> bool {
+    match value {
+        Value::String(text) => {
+            let masked = match redact_message(policy, key, text) {
+                Some(Cow::Owned(masked)) => masked,
+                Some(Cow::Borrowed(_)) => return true,
+                None => return false,
+            };
+            *text = masked;
+        }
+        Value::Array(items) => items.retain_mut(|item| redact_json(policy, key, item)),
+        Value::Object(map) => {
+            map.retain(|key, value| key == "name" || redact_json(policy, key, value))
+        }
+        _ => {}
+    }
+    true
+}
+
+/// The message is always kept, masked if its rule is to drop it.
+fn redact_message<'a>(policy: &RedactionPolicy, key: &str, value: &'a str) -> Option<Cow<'a, str>> {
+    match key {
+        "message" => Some(policy.redact_field(key, value)),
+        _ => policy.redact(key, value),
+    }
+}
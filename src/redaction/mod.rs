@@ -0,0 +1,778 @@
+use anyhow::{Context as _, Result, anyhow};
+use arc_swap::ArcSwapOption;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::tasklocal::with_current_telemetry_ctx;
+
+mod config;
+mod format;
+#[cfg(feature = "otlp")]
+mod processor;
+mod rules;
+mod strategy;
+mod tenant;
+
+use config::{first_error, warn};
+use rules::FieldRule;
+use strategy::{HmacKey, MASK};
+
+pub use config::{RedactionConfigError, self_test, strict_from_env, validate_redaction_config};
+pub use format::{RedactingFields, RedactingFormat, RedactingVisitor};
+#[cfg(feature = "otlp")]
+pub use processor::{RedactingSpanProcessor, RedactionLogProcessor};
+pub use strategy::RedactionStrategy;
+pub use tenant::RedactionPolicies;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionMode {
+    #[default]
+    Off,
+    Strict,
+    Allowlist,
+}
+
+/// What to redact and how: field-name rules and value patterns, each mapped
+/// to a [`RedactionStrategy`].
+///
+/// A value is checked against the allowlist (allowlist mode only), then the
+/// field rules in the order they were added, and only then the patterns.
+/// Values holding a JSON object or array are redacted member by member, with
+/// nested fields named by path, e.g. `payload.user.email`.
+///
+/// ```
+/// use greentic_telemetry::redaction::{RedactionMode, RedactionPolicy, RedactionStrategy};
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let policy = RedactionPolicy::new(RedactionMode::Strict)
+///     .with_hmac_key("rotate-me")
+///     .with_pattern_strategy("email", RedactionStrategy::Hash)?
+///     .with_pattern(r"\bacct-\d+\b", RedactionStrategy::Truncate(4))?
+///     .with_field("*.password", RedactionStrategy::Drop)?
+///     .with_field("user.*", RedactionStrategy::Hash)?;
+/// assert_eq!(policy.redact("note", "acct-123456").as_deref(), Some("***3456"));
+/// assert_eq!(policy.redact("db.password", "hunter2"), None);
+/// assert_eq!(
+///     policy.redact("payload", r#"{"db":{"password":"x"},"n":1}"#).as_deref(),
+///     Some(r#"{"db":{},"n":1}"#)
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RedactionPolicy {
+    mode: RedactionMode,
+    allowlist: Vec<String>,
+    patterns: Vec<Pattern>,
+    /// All of `patterns`, so values matching none are passed over in one scan.
+    pattern_set: RegexSet,
+    fields: Vec<FieldRule>,
+    strategy: RedactionStrategy,
+    hmac_key: Option<HmacKey>,
+}
+
+#[derive(Clone, Debug)]
+struct Pattern {
+    name: Option<String>,
+    regex: Regex,
+    /// Falls back to the policy's default strategy.
+    strategy: Option<RedactionStrategy>,
+}
+
+/// `None` until installed or first used. Readers never block, and swapping
+/// in new policies does not wait for records being redacted.
+static POLICIES: ArcSwapOption<RedactionPolicies> = ArcSwapOption::const_empty();
+
+/// Built-in patterns applied in strict and allowlist modes, by name.
+static DEFAULT_PATTERNS: Lazy<Vec<(&str, Regex)>> = Lazy::new(|| {
+    vec![
+        (
+            "email",
+            Regex::new(r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}").unwrap(),
+        ),
+        (
+            "bearer",
+            Regex::new(r"(?i)bearer\s+[a-z0-9._\-]+\b").unwrap(),
+        ),
+        (
+            "api_key",
+            Regex::new(r"(?i)(api[-_]?key|token)\s*[:=]\s*[a-z0-9._\-]+\b").unwrap(),
+        ),
+        ("phone", Regex::new(r"\+?\d[\d\-\s]{7,14}\d").unwrap()),
+    ]
+});
+
+/// Install the policies configured by `PII_POLICY_FILE` or the other `PII_*`
+/// environment variables, see [`RedactionPolicies::from_env`]. The first
+/// installed policies win; without any, the env ones are loaded on first use.
+/// Invalid settings and a failing [`self_test`] are only logged; use
+/// [`try_init_from_env`] to refuse them.
+pub fn init_from_env() {
+    if let Err(err) = self_test() {
+        tracing::error!("PII redaction self-test failed: {err}");
+    }
+    init_with_policies(RedactionPolicies::from_env());
+}
+
+/// Like [`init_from_env`], but fails on the first invalid setting or a
+/// failing [`self_test`] instead of skipping it. Returns whether the
+/// policies were installed.
+pub fn try_init_from_env() -> Result<bool, RedactionConfigError> {
+    self_test()?;
+    Ok(init_with_policies(RedactionPolicies::try_from_env()?))
+}
+
+/// Install `policy` for every tenant unless policies are already in place;
+/// returns whether it was.
+pub fn init_with_policy(policy: RedactionPolicy) -> bool {
+    init_with_policies(policy.into())
+}
+
+/// Install `policies` unless some are already in place; returns whether they were.
+pub fn init_with_policies(policies: RedactionPolicies) -> bool {
+    let previous = POLICIES.compare_and_swap(&None::<Arc<_>>, Some(Arc::new(policies)));
+    previous.is_none()
+}
+
+/// Replace the installed policies at runtime. Records already being
+/// redacted finish with the previous ones.
+pub fn set_policies(policies: RedactionPolicies) {
+    POLICIES.store(Some(Arc::new(policies)));
+}
+
+/// Load a policy file, see [`RedactionPolicies::from_json`], and swap it in.
+/// The installed policies stay in place if the file is invalid.
+pub fn reload_from_file(path: impl AsRef<Path>) -> Result<()> {
+    set_policies(RedactionPolicies::from_file(path)?);
+    Ok(())
+}
+
+impl RedactionPolicy {
+    /// Policy for `mode`, with the built-in patterns unless `mode` is off.
+    pub fn new(mode: RedactionMode) -> Self {
+        let mut policy = Self {
+            mode,
+            ..Self::default()
+        };
+        if mode != RedactionMode::Off {
+            policy.extend_patterns(DEFAULT_PATTERNS.iter().map(|(name, regex)| Pattern {
+                name: Some(name.to_string()),
+                regex: regex.clone(),
+                strategy: None,
+            }));
+        }
+        policy
+    }
+
+    /// Read `PII_REDACTION_MODE`, `PII_ALLOWLIST_FIELDS`, `PII_MASK_REGEXES`,
+    /// `PII_REDACTION_STRATEGY` (default strategy), `PII_PATTERN_STRATEGIES`
+    /// (`email=preserve,phone=truncate:2`), `PII_FIELD_STRATEGIES`
+    /// (`*.password=drop,user.*=hash,user.id=keep`) and `PII_HMAC_KEY`.
+    /// Invalid entries are skipped with a warning, and an unknown mode turns
+    /// redaction off; [`RedactionPolicy::try_from_env`] rejects them.
+    pub fn from_env() -> Self {
+        Self::load_env(&mut warn)
+    }
+
+    /// Like [`RedactionPolicy::from_env`], failing on the first invalid setting.
+    pub fn try_from_env() -> Result<Self, RedactionConfigError> {
+        first_error(Self::load_env)
+    }
+
+    pub(crate) fn load_env(report: &mut dyn FnMut(RedactionConfigError)) -> Self {
+        let mode = match std::env::var("PII_REDACTION_MODE") {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "" | "off" | "none" => RedactionMode::Off,
+                "strict" => RedactionMode::Strict,
+                "allowlist" => RedactionMode::Allowlist,
+                _ => {
+                    report(RedactionConfigError::UnknownMode {
+                        var: "PII_REDACTION_MODE",
+                        value,
+                    });
+                    RedactionMode::Off
+                }
+            },
+            Err(_) => RedactionMode::Off,
+        };
+
+        let mut policy = Self::new(mode);
+        if mode == RedactionMode::Allowlist
+            && let Ok(value) = std::env::var("PII_ALLOWLIST_FIELDS")
+        {
+            policy = policy.with_allowlist(value.split(','));
+        }
+        if let Some(strategy) = env_strategy("PII_REDACTION_STRATEGY", report) {
+            policy.strategy = strategy;
+        }
+        for (name, strategy) in env_strategy_map("PII_PATTERN_STRATEGIES", report) {
+            if let Err(err) = policy.set_pattern_strategy(&name, strategy) {
+                report(RedactionConfigError::InvalidEntry {
+                    var: "PII_PATTERN_STRATEGIES",
+                    entry: name,
+                    reason: format!("{err:#}"),
+                });
+            }
+        }
+        for (field, strategy) in env_strategy_map("PII_FIELD_STRATEGIES", report) {
+            match FieldRule::new(&field, strategy) {
+                Ok(rule) => policy.push_field_rule(rule),
+                Err(err) => report(RedactionConfigError::InvalidEntry {
+                    var: "PII_FIELD_STRATEGIES",
+                    entry: field,
+                    reason: format!("{err:#}"),
+                }),
+            }
+        }
+        if let Ok(key) = std::env::var("PII_HMAC_KEY")
+            && !key.is_empty()
+        {
+            policy = policy.with_hmac_key(key);
+        }
+
+        let custom = std::env::var("PII_MASK_REGEXES").unwrap_or_default();
+        let mut patterns = Vec::new();
+        for pattern in custom.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match Regex::new(pattern) {
+                Ok(regex) => patterns.push(Pattern {
+                    name: None,
+                    regex,
+                    strategy: None,
+                }),
+                Err(source) => report(RedactionConfigError::InvalidRegex {
+                    var: "PII_MASK_REGEXES",
+                    pattern: pattern.to_string(),
+                    source,
+                }),
+            }
+        }
+        policy.extend_patterns(patterns);
+        policy
+    }
+
+    pub fn mode(&self) -> RedactionMode {
+        self.mode
+    }
+
+    /// Whether anything can be redacted: a mode other than off, or field
+    /// rules, which apply in every mode.
+    pub fn is_active(&self) -> bool {
+        self.mode != RedactionMode::Off || !self.fields.is_empty()
+    }
+
+    /// Field names, matched case-insensitively, that allowlist mode leaves as is.
+    pub fn with_allowlist<I>(mut self, fields: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.allowlist.extend(
+            fields
+                .into_iter()
+                .map(|field| field.as_ref().trim().to_ascii_lowercase())
+                .filter(|field| !field.is_empty()),
+        );
+        self
+    }
+
+    /// Strategy for pattern matches without a strategy of their own.
+    pub fn with_strategy(mut self, strategy: RedactionStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Strategy for a built-in pattern: `email`, `bearer`, `api_key` or `phone`.
+    /// Any other name is an error rather than a pattern left at the default.
+    pub fn with_pattern_strategy(
+        mut self,
+        name: &str,
+        strategy: RedactionStrategy,
+    ) -> Result<Self> {
+        self.set_pattern_strategy(name, strategy)?;
+        Ok(self)
+    }
+
+    fn set_pattern_strategy(&mut self, name: &str, strategy: RedactionStrategy) -> Result<()> {
+        if !DEFAULT_PATTERNS.iter().any(|(builtin, _)| *builtin == name) {
+            let known: Vec<&str> = DEFAULT_PATTERNS
+                .iter()
+                .map(|(builtin, _)| *builtin)
+                .collect();
+            return Err(anyhow!(
+                "unknown pattern '{name}', expected one of {}",
+                known.join(", ")
+            ));
+        }
+        for pattern in &mut self.patterns {
+            if pattern.name.as_deref() == Some(name) {
+                pattern.strategy = Some(strategy.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Redact matches of `pattern` with `strategy`.
+    pub fn with_pattern(mut self, pattern: &str, strategy: RedactionStrategy) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("invalid pattern '{pattern}'"))?;
+        self.extend_patterns([Pattern {
+            name: None,
+            regex,
+            strategy: Some(strategy),
+        }]);
+        Ok(self)
+    }
+
+    fn extend_patterns(&mut self, patterns: impl IntoIterator<Item = Pattern>) {
+        self.patterns.extend(patterns);
+        self.pattern_set = RegexSet::new(self.patterns.iter().map(|p| p.regex.as_str()))
+            .expect("patterns compile individually");
+    }
+
+    /// Apply `strategy` to the whole value of every field matching `glob`,
+    /// e.g. `http.request.header.authorization`, `*.password` or `user.*`.
+    /// Use [`RedactionStrategy::Keep`] to exempt fields from the patterns.
+    pub fn with_field(mut self, glob: &str, strategy: RedactionStrategy) -> Result<Self> {
+        self.push_field_rule(FieldRule::new(glob, strategy)?);
+        Ok(self)
+    }
+
+    /// Add `rule`, replacing any rule for the same glob.
+    fn push_field_rule(&mut self, rule: FieldRule) {
+        self.fields
+            .retain(|existing| existing.glob() != rule.glob());
+        self.fields.push(rule);
+    }
+
+    /// Secret for [`RedactionStrategy::Hash`]; hashing masks without one.
+    pub fn with_hmac_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        self.hmac_key = Some(HmacKey::new(key));
+        self
+    }
+
+    /// Redacted form of `value` for the field `key`; `None` means the field
+    /// should be dropped. Values left as they are come back borrowed.
+    ///
+    /// Field rules apply in every mode; the mode only decides whether the
+    /// value patterns run.
+    pub fn redact<'a>(&self, key: &str, value: &'a str) -> Option<Cow<'a, str>> {
+        if !self.is_active() || self.is_allowed(key) {
+            return Some(Cow::Borrowed(value));
+        }
+        if let Some(rule) = self.field_rule(key) {
+            return rule.strategy.apply(value, self.hmac_key.as_ref());
+        }
+        if let Some(mut json) = parse_json_container(value) {
+            let mut changed = false;
+            if !self.redact_json(key, &mut json, &mut changed) {
+                return None;
+            }
+            return Some(match changed {
+                true => Cow::Owned(json.to_string()),
+                false => Cow::Borrowed(value),
+            });
+        }
+        if self.mode == RedactionMode::Off {
+            return Some(Cow::Borrowed(value));
+        }
+        self.apply_patterns(value)
+    }
+
+    /// Like [`RedactionPolicy::redact`], for places that cannot drop a
+    /// value; a dropped value is masked instead.
+    pub(crate) fn redact_field<'a>(&self, key: &str, value: &'a str) -> Cow<'a, str> {
+        self.redact(key, value).unwrap_or(Cow::Borrowed(MASK))
+    }
+
+    fn is_allowed(&self, key: &str) -> bool {
+        self.mode == RedactionMode::Allowlist
+            && self
+                .allowlist
+                .iter()
+                .any(|field| field.eq_ignore_ascii_case(key))
+    }
+
+    fn field_rule(&self, key: &str) -> Option<&FieldRule> {
+        self.fields.iter().find(|rule| rule.matches(key))
+    }
+
+    /// Redact `value`, found at `path`, in place; `false` means drop it.
+    /// Sets `changed` when anything was rewritten or dropped.
+    fn redact_json(&self, path: &str, value: &mut serde_json::Value, changed: &mut bool) -> bool {
+        use serde_json::Value;
+
+        if let Value::String(text) = value {
+            let masked = match self.redact(path, text) {
+                Some(Cow::Owned(masked)) => masked,
+                Some(Cow::Borrowed(_)) => return true,
+                None => {
+                    *changed = true;
+                    return false;
+                }
+            };
+            *text = masked;
+            *changed = true;
+            return true;
+        }
+        if self.is_allowed(path) {
+            return true;
+        }
+        if let Some(rule) = self.field_rule(path) {
+            if rule.strategy == RedactionStrategy::Keep {
+                return true;
+            }
+            *changed = true;
+            return match rule
+                .strategy
+                .apply(&value.to_string(), self.hmac_key.as_ref())
+            {
+                Some(masked) => {
+                    *value = Value::String(masked.into_owned());
+                    true
+                }
+                None => false,
+            };
+        }
+        match value {
+            Value::Array(items) => items.retain_mut(|item| self.redact_json(path, item, changed)),
+            Value::Object(map) => map
+                .retain(|key, member| self.redact_json(&format!("{path}.{key}"), member, changed)),
+            _ => {}
+        }
+        true
+    }
+
+    /// Rewrite every match in one pass, so a rewritten value is never matched
+    /// again; where matches overlap the earlier pattern wins. Only patterns
+    /// the combined set reports are searched.
+    fn apply_patterns<'a>(&self, value: &'a str) -> Option<Cow<'a, str>> {
+        let hits = self.pattern_set.matches(value);
+        if !hits.matched_any() {
+            return Some(Cow::Borrowed(value));
+        }
+
+        let mut matches: Vec<(Range<usize>, &RedactionStrategy)> = Vec::new();
+        for pattern in hits.iter().map(|index| &self.patterns[index]) {
+            let strategy = pattern.strategy.as_ref().unwrap_or(&self.strategy);
+            for found in pattern.regex.find_iter(value) {
+                let free = matches
+                    .iter()
+                    .all(|(range, _)| found.end() <= range.start || found.start() >= range.end);
+                if !found.is_empty() && free {
+                    matches.push((found.range(), strategy));
+                }
+            }
+        }
+        if matches.is_empty() {
+            return Some(Cow::Borrowed(value));
+        }
+
+        matches.sort_by_key(|(range, _)| range.start);
+        let mut masked = String::with_capacity(value.len());
+        let mut last = 0;
+        for (range, strategy) in matches {
+            masked.push_str(&value[last..range.start]);
+            masked.push_str(&strategy.apply(&value[range.clone()], self.hmac_key.as_ref())?);
+            last = range.end;
+        }
+        masked.push_str(&value[last..]);
+        Some(Cow::Owned(masked))
+    }
+}
+
+/// A JSON object or array serialised into a string attribute.
+fn parse_json_container(value: &str) -> Option<serde_json::Value> {
+    if !value.trim_start().starts_with(['{', '[']) {
+        return None;
+    }
+    serde_json::from_str(value)
+        .ok()
+        .filter(|json: &serde_json::Value| json.is_object() || json.is_array())
+}
+
+fn env_strategy(
+    var: &'static str,
+    report: &mut dyn FnMut(RedactionConfigError),
+) -> Option<RedactionStrategy> {
+    let value = std::env::var(var).ok()?;
+    match value.parse() {
+        Ok(strategy) => Some(strategy),
+        Err(err) => {
+            report(RedactionConfigError::InvalidEntry {
+                var,
+                entry: value,
+                reason: format!("{err:#}"),
+            });
+            None
+        }
+    }
+}
+
+fn env_strategy_map(
+    var: &'static str,
+    report: &mut dyn FnMut(RedactionConfigError),
+) -> Vec<(String, RedactionStrategy)> {
+    let Ok(value) = std::env::var(var) else {
+        return Vec::new();
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected name=strategy"))
+                .and_then(|(name, strategy)| Ok((name.trim().to_string(), strategy.parse()?)));
+            match parsed {
+                Ok(pair) => Some(pair),
+                Err(err) => {
+                    report(RedactionConfigError::InvalidEntry {
+                        var,
+                        entry: entry.to_string(),
+                        reason: format!("{err:#}"),
+                    });
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The installed policies.
+pub fn policies() -> Arc<RedactionPolicies> {
+    if let Some(policies) = POLICIES.load_full() {
+        return policies;
+    }
+    init_from_env();
+    POLICIES.load_full().expect("redaction policies installed")
+}
+
+/// Borrow the installed policies without touching their reference count.
+fn with_policies<R>(f: impl FnOnce(&RedactionPolicies) -> R) -> R {
+    if let Some(policies) = &*POLICIES.load() {
+        return f(policies);
+    }
+    f(&policies())
+}
+
+/// The installed policy for `tenant`, or the default one.
+pub fn policy_for(tenant: Option<&str>) -> Arc<RedactionPolicy> {
+    with_policies(|policies| {
+        policies
+            .policy_for(tenant.filter(|tenant| !tenant.is_empty()))
+            .clone()
+    })
+}
+
+/// The installed policy for the tenant of the current `TelemetryCtx`.
+pub fn current_policy() -> Arc<RedactionPolicy> {
+    with_current_telemetry_ctx(|ctx| policy_for(ctx.map(|ctx| ctx.tenant.as_str())))
+}
+
+/// Whether any tenant's values are being masked; exporters skip the work
+/// when not.
+pub fn is_active() -> bool {
+    with_policies(RedactionPolicies::is_active)
+}
+
+/// [`RedactionPolicy::redact`] with the current tenant's policy.
+pub fn redact<'a>(key: &str, value: &'a str) -> Option<Cow<'a, str>> {
+    current_policy().redact(key, value)
+}
+
+/// Like [`redact`], for places that cannot drop a value; a dropped value is
+/// masked instead.
+pub fn redact_field<'a>(key: &str, value: &'a str) -> Cow<'a, str> {
+    current_policy().redact_field(key, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_masks_email_phone_and_token() {
+        let policy = RedactionPolicy::new(RedactionMode::Strict);
+
+        let masked = policy
+            .redact(
+                "message",
+                "Email alice@example.com with bearer ABC123 and call +12345678901",
+            )
+            .unwrap();
+
+        assert!(!masked.contains("alice@example.com"));
+        assert!(!masked.contains("ABC123"));
+        assert!(!masked.contains("+12345678901"));
+        assert!(masked.contains("[REDACTED]"));
+    }
+
+    #[test]
+    fn allowlist_keeps_fields() {
+        let policy = RedactionPolicy::new(RedactionMode::Allowlist).with_allowlist(["user_id"]);
+
+        let masked = policy.redact("note", "User token = secret").unwrap();
+        assert!(masked.contains("[REDACTED]"));
+
+        let field_value = policy.redact("USER_ID", "alice@example.com");
+        assert_eq!(field_value.as_deref(), Some("alice@example.com"));
+    }
+
+    #[test]
+    fn custom_regex_masks_access_token() {
+        let policy = RedactionPolicy::new(RedactionMode::Strict)
+            .with_pattern(r"(?i)secret\s*[:=]\s*[a-z0-9]+\b", RedactionStrategy::Mask)
+            .unwrap();
+
+        let masked = policy.redact("note", "secret=abcdef");
+        assert_eq!(masked.as_deref(), Some("[REDACTED]"));
+    }
+
+    #[test]
+    fn strategies_apply_per_pattern_and_field() {
+        let policy = RedactionPolicy::new(RedactionMode::Strict)
+            .with_hmac_key("k")
+            .with_pattern_strategy("email", RedactionStrategy::Preserve)
+            .unwrap()
+            .with_pattern_strategy("phone", RedactionStrategy::Truncate(2))
+            .unwrap()
+            .with_field("user.id", RedactionStrategy::Hash)
+            .unwrap()
+            .with_field("password", RedactionStrategy::Drop)
+            .unwrap();
+
+        assert_eq!(
+            policy
+                .redact("note", "mail bob@example.com or +12345678901")
+                .as_deref(),
+            Some("mail b**@example.com or ***01")
+        );
+        let id = policy.redact("user.id", "u-42").unwrap();
+        assert!(id.starts_with("hmac:"));
+        assert_eq!(policy.redact("User.Id", "u-42"), Some(id));
+        assert_eq!(policy.redact("password", "hunter2"), None);
+
+        let dropping =
+            RedactionPolicy::new(RedactionMode::Strict).with_strategy(RedactionStrategy::Drop);
+        assert_eq!(dropping.redact("note", "ping bob@example.com"), None);
+        assert_eq!(
+            dropping.redact("note", "nothing here").as_deref(),
+            Some("nothing here")
+        );
+    }
+
+    #[test]
+    fn off_mode_still_applies_field_rules() {
+        let off = RedactionPolicy::new(RedactionMode::Off);
+        assert!(!off.is_active());
+        assert_eq!(
+            off.redact("note", "bob@example.com").as_deref(),
+            Some("bob@example.com")
+        );
+
+        let policy = off
+            .with_field("*.password", RedactionStrategy::Drop)
+            .unwrap();
+        assert!(policy.is_active());
+        assert_eq!(policy.redact("user.password", "hunter2"), None);
+        assert_eq!(
+            policy
+                .redact("body", r#"{"user":{"password":"hunter2"}}"#)
+                .as_deref(),
+            Some(r#"{"user":{}}"#)
+        );
+        assert_eq!(
+            policy.redact("note", "bob@example.com").as_deref(),
+            Some("bob@example.com"),
+            "patterns stay off"
+        );
+    }
+
+    #[test]
+    fn field_rules_take_precedence_over_patterns() {
+        let policy = RedactionPolicy::new(RedactionMode::Strict)
+            .with_field("user.contact", RedactionStrategy::Keep)
+            .unwrap()
+            .with_field("user.*", RedactionStrategy::Mask)
+            .unwrap()
+            .with_field("http.request.header.authorization", RedactionStrategy::Drop)
+            .unwrap();
+
+        assert_eq!(
+            policy.redact("user.contact", "bob@example.com").as_deref(),
+            Some("bob@example.com")
+        );
+        assert_eq!(
+            policy.redact("user.name", "Bob").as_deref(),
+            Some("[REDACTED]")
+        );
+        assert_eq!(
+            policy.redact("http.request.header.authorization", "Basic eA=="),
+            None
+        );
+        assert_eq!(
+            policy.redact("note", "hi bob@example.com").as_deref(),
+            Some("hi [REDACTED]")
+        );
+    }
+
+    #[test]
+    fn json_values_are_redacted_recursively() {
+        let policy = RedactionPolicy::new(RedactionMode::Strict)
+            .with_field("*.password", RedactionStrategy::Drop)
+            .unwrap()
+            .with_field("payload.card", RedactionStrategy::Mask)
+            .unwrap();
+
+        let payload = r#"{"user":{"email":"bob@example.com","password":"x","age":42},"card":{"pan":"4111"},"tags":["ok","mail a@b.io"]}"#;
+        let redacted: serde_json::Value =
+            serde_json::from_str(&policy.redact("payload", payload).unwrap()).unwrap();
+        assert_eq!(
+            redacted,
+            serde_json::json!({
+                "user": {"email": "[REDACTED]", "age": 42},
+                "card": "[REDACTED]",
+                "tags": ["ok", "mail [REDACTED]"],
+            })
+        );
+
+        assert_eq!(
+            policy.redact("note", "[not json").as_deref(),
+            Some("[not json")
+        );
+    }
+
+    #[test]
+    fn untouched_values_are_borrowed() {
+        let policy = RedactionPolicy::new(RedactionMode::Strict)
+            .with_field("user.id", RedactionStrategy::Keep)
+            .unwrap();
+
+        assert!(matches!(
+            policy.redact("note", "nothing to see here"),
+            Some(Cow::Borrowed(_))
+        ));
+        assert!(matches!(
+            policy.redact("user.id", "bob@example.com"),
+            Some(Cow::Borrowed(_))
+        ));
+        assert!(matches!(
+            policy.redact("note", "mail bob@example.com"),
+            Some(Cow::Owned(_))
+        ));
+        assert!(matches!(
+            RedactionPolicy::new(RedactionMode::Off).redact("note", "bob@example.com"),
+            Some(Cow::Borrowed(_))
+        ));
+        assert!(matches!(
+            policy.redact("payload", r#"{"user": {"id": 7, "plan": "pro"}}"#),
+            Some(Cow::Borrowed(_))
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+//! Redaction of span and log data ahead of the OpenTelemetry exporters.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use opentelemetry::logs::{AnyValue, LogRecord as _, Logger as _, LoggerProvider as _};
+use opentelemetry::{Array, Context, InstrumentationScope, KeyValue, Value};
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord, SdkLogger, SdkLoggerProvider};
+use opentelemetry_sdk::resource::Resource;
+use opentelemetry_sdk::trace::{Span, SpanData, SpanProcessor};
+
+use super::strategy::MASK;
+use super::{RedactionPolicy, current_policy, is_active, policy_for};
+use crate::context::context_keys;
+
+/// Key under which span event names and log bodies, i.e. messages, are redacted.
+const MESSAGE_KEY: &str = "message";
+
+/// The policy of the tenant named by a record's tenant attribute, else of the
+/// current `TelemetryCtx`.
+fn tenant_policy(tenant: Option<&str>) -> Arc<RedactionPolicy> {
+    match tenant.filter(|tenant| !tenant.is_empty()) {
+        Some(tenant) => policy_for(Some(tenant)),
+        None => current_policy(),
+    }
+}
+
+/// Masks span attributes, event names and event attributes, and the status
+/// message, before handing the span to the wrapped exporting processor.
+#[derive(Debug)]
+pub struct RedactingSpanProcessor<P> {
+    inner: P,
+}
+
+impl<P> RedactingSpanProcessor<P> {
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+}
+
+impl<P: SpanProcessor> SpanProcessor for RedactingSpanProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &Context) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        let policy = is_active().then(|| {
+            let keys = context_keys();
+            let tenant = span
+                .attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == keys.tenant)
+                .map(|kv| kv.value.as_str());
+            tenant_policy(tenant.as_deref())
+        });
+        if let Some(policy) = policy.filter(|policy| policy.is_active()) {
+            redact_attributes(&policy, &mut span.attributes);
+            for event in &mut span.events.events {
+                redact_message(&policy, &mut event.name);
+                redact_attributes(&policy, &mut event.attributes);
+            }
+            if let opentelemetry::trace::Status::Error { description } = &mut span.status {
+                redact_message(&policy, description);
+            }
+        }
+        self.inner.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Mask string values and remove attributes whose rule is to drop them.
+fn redact_attributes(policy: &RedactionPolicy, attributes: &mut Vec<KeyValue>) {
+    attributes.retain_mut(|KeyValue { key, value, .. }| match value {
+        Value::String(text) => redact_in_place(policy, key.as_str(), text),
+        Value::Array(Array::String(items)) => {
+            items.retain_mut(|text| redact_in_place(policy, key.as_str(), text));
+            true
+        }
+        _ => true,
+    });
+}
+
+/// Replace `text` only if redaction rewrote it; `false` means drop it.
+fn redact_in_place<T: AsRef<str> + From<String>>(
+    policy: &RedactionPolicy,
+    key: &str,
+    text: &mut T,
+) -> bool {
+    let masked = match policy.redact(key, text.as_ref()) {
+        Some(Cow::Owned(masked)) => masked,
+        Some(Cow::Borrowed(_)) => return true,
+        None => return false,
+    };
+    *text = masked.into();
+    true
+}
+
+/// Messages are never dropped, only masked.
+fn redact_message(policy: &RedactionPolicy, text: &mut Cow<'static, str>) {
+    if !redact_in_place(policy, MESSAGE_KEY, text) {
+        *text = Cow::Borrowed(MASK);
+    }
+}
+
+/// Source of empty records, as `SdkLogRecord` has no public constructor and
+/// attributes cannot be replaced in place.
+static BLANK: Lazy<SdkLogger> = Lazy::new(|| {
+    SdkLoggerProvider::builder()
+        .build()
+        .logger("greentic-telemetry")
+});
+
+/// Masks the body and attributes of log records. Register it after any
+/// processor that adds attributes and before the exporting one.
+#[derive(Debug, Default)]
+pub struct RedactionLogProcessor;
+
+impl LogProcessor for RedactionLogProcessor {
+    fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+        if !is_active() {
+            return;
+        }
+        let keys = context_keys();
+        let tenant = record
+            .attributes_iter()
+            .find(|(key, _)| key.as_str() == keys.tenant)
+            .and_then(|(_, value)| match value {
+                AnyValue::String(tenant) => Some(tenant.as_str()),
+                _ => None,
+            });
+        let policy = tenant_policy(tenant);
+        if !policy.is_active() {
+            return;
+        }
+
+        let body = record
+            .body()
+            .map(|body| redact_any(&policy, MESSAGE_KEY, body).unwrap_or_else(|| MASK.into()));
+        let attributes: Vec<_> = record
+            .attributes_iter()
+            .filter_map(|(key, value)| {
+                Some((key.clone(), redact_any(&policy, key.as_str(), value)?))
+            })
+            .collect();
+        let unchanged = body.as_ref() == record.body()
+            && attributes.len() == record.attributes_iter().count()
+            && attributes
+                .iter()
+                .zip(record.attributes_iter())
+                .all(|((_, masked), (_, value))| masked == value);
+        if unchanged {
+            return;
+        }
+
+        let mut redacted = BLANK.create_log_record();
+        if let Some(name) = record.event_name() {
+            redacted.set_event_name(name);
+        }
+        if let Some(target) = record.target() {
+            redacted.set_target(target.clone());
+        }
+        if let Some(timestamp) = record.timestamp() {
+            redacted.set_timestamp(timestamp);
+        }
+        if let Some(timestamp) = record.observed_timestamp() {
+            redacted.set_observed_timestamp(timestamp);
+        }
+        if let Some(text) = record.severity_text() {
+            redacted.set_severity_text(text);
+        }
+        if let Some(number) = record.severity_number() {
+            redacted.set_severity_number(number);
+        }
+        if let Some(trace) = record.trace_context() {
+            redacted.set_trace_context(trace.trace_id, trace.span_id, trace.trace_flags);
+        }
+        if let Some(body) = body {
+            redacted.set_body(body);
+        }
+        redacted.add_attributes(attributes);
+        *record = redacted;
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+/// `None` when the value is to be dropped; dropped list and map entries are
+/// removed from their container.
+fn redact_any(policy: &RedactionPolicy, key: &str, value: &AnyValue) -> Option<AnyValue> {
+    Some(match value {
+        AnyValue::String(text) => match policy.redact(key, text.as_str())? {
+            Cow::Owned(masked) => AnyValue::String(masked.into()),
+            Cow::Borrowed(_) => value.clone(),
+        },
+        AnyValue::ListAny(items) => AnyValue::ListAny(Box::new(
+            items
+                .iter()
+                .filter_map(|item| redact_any(policy, key, item))
+                .collect(),
+        )),
+        AnyValue::Map(map) => AnyValue::Map(Box::new(
+            map.iter()
+                .filter_map(|(key, value)| {
+                    Some((key.clone(), redact_any(policy, key.as_str(), value)?))
+                })
+                .collect(),
+        )),
+        other => other.clone(),
+    })
+}
@@ -0,0 +1,59 @@
+//! Field-name rules, matched with globs such as `*.password` or `user.*`.
+
+use anyhow::{Context as _, Result};
+use globset::{GlobBuilder, GlobMatcher};
+
+use super::RedactionStrategy;
+
+#[derive(Clone, Debug)]
+pub(crate) struct FieldRule {
+    glob: String,
+    matcher: GlobMatcher,
+    pub(crate) strategy: RedactionStrategy,
+}
+
+impl FieldRule {
+    /// `*` matches any run of characters, dots included; matching ignores case.
+    pub(crate) fn new(glob: &str, strategy: RedactionStrategy) -> Result<Self> {
+        let glob = glob.trim();
+        let matcher = GlobBuilder::new(glob)
+            .case_insensitive(true)
+            .literal_separator(false)
+            .build()
+            .with_context(|| format!("invalid field glob '{glob}'"))?
+            .compile_matcher();
+        Ok(Self {
+            glob: glob.to_string(),
+            matcher,
+            strategy,
+        })
+    }
+
+    pub(crate) fn glob(&self) -> &str {
+        &self.glob
+    }
+
+    pub(crate) fn matches(&self, key: &str) -> bool {
+        self.matcher.is_match(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_dotted_field_names() {
+        let rule = |glob| FieldRule::new(glob, RedactionStrategy::Mask).unwrap();
+        assert!(rule("*.password").matches("db.password"));
+        assert!(rule("*.password").matches("svc.db.Password"));
+        assert!(!rule("*.password").matches("password"));
+        assert!(rule("user.*").matches("user.address.city"));
+        assert!(!rule("user.*").matches("username"));
+        assert!(
+            rule("http.request.header.authorization").matches("HTTP.Request.Header.Authorization")
+        );
+        assert!(rule("card_{number,cvv}").matches("card_cvv"));
+        assert!(FieldRule::new("[oops", RedactionStrategy::Mask).is_err());
+    }
+}
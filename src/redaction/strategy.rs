@@ -0,0 +1,229 @@
+//! How a sensitive value is rewritten once a pattern or field rule selects it.
+
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Deserializer, de};
+use sha2::Sha256;
+
+pub(crate) const MASK: &str = "[REDACTED]";
+
+/// Hex digits of the HMAC kept by [`RedactionStrategy::Hash`]; 64 bits is
+/// plenty to correlate values without bloating attributes.
+const HASH_HEX_LEN: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum RedactionStrategy {
+    /// Replace with `[REDACTED]`.
+    #[default]
+    Mask,
+    /// Keyed HMAC-SHA256 pseudonym, `hmac:<16 hex digits>`, stable for a
+    /// given key so the same value correlates across spans.
+    Hash,
+    /// Keep only the last `n` characters, e.g. `***4242`.
+    Truncate(usize),
+    /// Mask characters but keep the shape: `a****@example.com`,
+    /// `+*********01`.
+    Preserve,
+    /// Remove the attribute or field altogether.
+    Drop,
+    /// Leave the value as is; lets a field rule exempt a field from patterns.
+    Keep,
+}
+
+impl FromStr for RedactionStrategy {
+    type Err = anyhow::Error;
+
+    /// `mask`, `hash`, `truncate[:n]` (default 4), `preserve`, `drop` or `keep`.
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let (name, arg) = match value.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (value.as_str(), None),
+        };
+        match (name, arg) {
+            ("mask", None) => Ok(Self::Mask),
+            ("hash", None) => Ok(Self::Hash),
+            ("truncate", None) => Ok(Self::Truncate(4)),
+            ("truncate", Some(n)) => n
+                .parse()
+                .map(Self::Truncate)
+                .map_err(|_| anyhow!("invalid truncate length '{n}'")),
+            ("preserve", None) => Ok(Self::Preserve),
+            ("drop", None) => Ok(Self::Drop),
+            ("keep", None) => Ok(Self::Keep),
+            _ => Err(anyhow!(
+                "unknown redaction strategy '{value}', expected mask, hash, truncate[:n], preserve, drop or keep"
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RedactionStrategy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|err| de::Error::custom(format!("{err:#}")))
+    }
+}
+
+/// Secret for [`RedactionStrategy::Hash`]; never printed.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct HmacKey(Arc<[u8]>);
+
+impl HmacKey {
+    pub(crate) fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into().into())
+    }
+}
+
+impl fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HmacKey(..)")
+    }
+}
+
+impl RedactionStrategy {
+    /// Rewrite `value`; `None` means drop. Hashing without a key masks
+    /// instead, as an unkeyed digest of low-entropy PII is reversible.
+    pub(crate) fn apply<'a>(&self, value: &'a str, key: Option<&HmacKey>) -> Option<Cow<'a, str>> {
+        Some(match self {
+            Self::Mask => Cow::Borrowed(MASK),
+            Self::Hash => match key {
+                Some(key) => Cow::Owned(pseudonym(value, key)),
+                None => Cow::Borrowed(MASK),
+            },
+            Self::Truncate(keep) => {
+                let len = value.chars().count();
+                if len > *keep {
+                    let tail: String = value.chars().skip(len - keep).collect();
+                    Cow::Owned(format!("***{tail}"))
+                } else {
+                    Cow::Borrowed("***")
+                }
+            }
+            Self::Preserve => Cow::Owned(preserve(value)),
+            Self::Drop => return None,
+            Self::Keep => Cow::Borrowed(value),
+        })
+    }
+}
+
+fn pseudonym(value: &str, key: &HmacKey) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key.0).expect("HMAC accepts any key length");
+    mac.update(value.as_bytes());
+    let digest = mac.finalize().into_bytes();
+    let mut out = String::with_capacity(5 + HASH_HEX_LEN);
+    out.push_str("hmac:");
+    for byte in &digest[..HASH_HEX_LEN / 2] {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+/// Emails keep the first character and the domain, phone numbers their
+/// punctuation and last two digits; anything else has every letter and
+/// digit masked.
+fn preserve(value: &str) -> String {
+    if let Some((local, domain)) = value.rsplit_once('@') {
+        let mut chars = local.chars();
+        let first = chars.next().map(String::from).unwrap_or_default();
+        return format!("{first}{}@{domain}", "*".repeat(chars.count()));
+    }
+
+    let is_phone = value
+        .chars()
+        .all(|c| c.is_ascii_digit() || "+-.() ".contains(c));
+    if is_phone {
+        let mut keep = 2;
+        let mut masked: Vec<char> = value.chars().collect();
+        for c in masked.iter_mut().rev() {
+            if c.is_ascii_digit() {
+                if keep > 0 {
+                    keep -= 1;
+                } else {
+                    *c = '*';
+                }
+            }
+        }
+        return masked.into_iter().collect();
+    }
+
+    value
+        .chars()
+        .map(|c| if c.is_alphanumeric() { '*' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_strategies() {
+        assert_eq!(
+            "Hash".parse::<RedactionStrategy>().unwrap(),
+            RedactionStrategy::Hash
+        );
+        assert_eq!(
+            "truncate:2".parse::<RedactionStrategy>().unwrap(),
+            RedactionStrategy::Truncate(2)
+        );
+        assert_eq!(
+            "truncate".parse::<RedactionStrategy>().unwrap(),
+            RedactionStrategy::Truncate(4)
+        );
+        assert!("truncate:x".parse::<RedactionStrategy>().is_err());
+        assert!("scramble".parse::<RedactionStrategy>().is_err());
+    }
+
+    #[test]
+    fn hashing_is_keyed_and_deterministic() {
+        let key = HmacKey::new("secret");
+        let hash = |value| RedactionStrategy::Hash.apply(value, Some(&key)).unwrap();
+        assert_eq!(hash("alice@example.com"), hash("alice@example.com"));
+        assert_ne!(hash("alice@example.com"), hash("bob@example.com"));
+        assert!(hash("alice@example.com").starts_with("hmac:"));
+        assert_eq!(hash("alice@example.com").len(), 5 + HASH_HEX_LEN);
+
+        let other = HmacKey::new("other");
+        assert_ne!(
+            RedactionStrategy::Hash.apply("alice@example.com", Some(&other)),
+            Some(hash("alice@example.com"))
+        );
+        assert_eq!(
+            RedactionStrategy::Hash
+                .apply("alice@example.com", None)
+                .as_deref(),
+            Some(MASK)
+        );
+    }
+
+    #[test]
+    fn truncates_and_preserves_format() {
+        let apply = |strategy: RedactionStrategy, value| strategy.apply(value, None).unwrap();
+        assert_eq!(
+            apply(RedactionStrategy::Truncate(4), "4111111111114242"),
+            "***4242"
+        );
+        assert_eq!(apply(RedactionStrategy::Truncate(4), "42"), "***");
+        assert_eq!(
+            apply(RedactionStrategy::Preserve, "alice@example.com"),
+            "a****@example.com"
+        );
+        assert_eq!(
+            apply(RedactionStrategy::Preserve, "+1 555-010-9901"),
+            "+* ***-***-**01"
+        );
+        assert_eq!(
+            apply(RedactionStrategy::Preserve, "Bearer ab.c1"),
+            "****** **.**"
+        );
+        assert_eq!(RedactionStrategy::Drop.apply("x", None), None);
+    }
+}
@@ -0,0 +1,268 @@
+//! Redaction policies keyed by tenant, loadable from a JSON file.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+use super::config::{first_error, warn};
+use super::{Pattern, RedactionConfigError, RedactionMode, RedactionPolicy, RedactionStrategy};
+
+/// Redaction policies keyed by `gt.tenant`, so each tenant can follow its own
+/// compliance regime. Records without a tenant, or whose tenant has no
+/// policy, use the default.
+#[derive(Clone, Debug, Default)]
+pub struct RedactionPolicies {
+    default: Arc<RedactionPolicy>,
+    tenants: HashMap<String, Arc<RedactionPolicy>>,
+}
+
+impl RedactionPolicies {
+    pub fn new(default: RedactionPolicy) -> Self {
+        Self {
+            default: Arc::new(default),
+            tenants: HashMap::new(),
+        }
+    }
+
+    /// Use `policy` for records tagged with `tenant`.
+    pub fn with_tenant(mut self, tenant: impl Into<String>, policy: RedactionPolicy) -> Self {
+        self.tenants.insert(tenant.into(), Arc::new(policy));
+        self
+    }
+
+    /// Key every policy with `key` for [`RedactionStrategy::Hash`].
+    pub fn with_hmac_key(mut self, key: impl Into<Vec<u8>>) -> Self {
+        let key = key.into();
+        for policy in std::iter::once(&mut self.default).chain(self.tenants.values_mut()) {
+            *policy = Arc::new((**policy).clone().with_hmac_key(key.clone()));
+        }
+        self
+    }
+
+    pub fn default_policy(&self) -> &Arc<RedactionPolicy> {
+        &self.default
+    }
+
+    /// The policy for `tenant`, falling back to the default.
+    pub fn policy_for(&self, tenant: Option<&str>) -> &Arc<RedactionPolicy> {
+        tenant
+            .and_then(|tenant| self.tenants.get(tenant))
+            .unwrap_or(&self.default)
+    }
+
+    /// Whether any policy masks values.
+    pub fn is_active(&self) -> bool {
+        std::iter::once(&self.default)
+            .chain(self.tenants.values())
+            .any(|policy| policy.is_active())
+    }
+
+    /// Parse a policy file:
+    ///
+    /// ```json
+    /// {
+    ///   "default": { "mode": "strict", "fields": [{ "field": "*.password", "strategy": "drop" }] },
+    ///   "tenants": {
+    ///     "clinic": { "mode": "strict", "strategy": "hash", "patterns": [{ "regex": "\\bMRN-\\d+\\b" }] },
+    ///     "internal-test": { "mode": "off" }
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Policies take the same settings as the `PII_*` variables; the HMAC key
+    /// stays out of the file, see [`RedactionPolicies::with_hmac_key`].
+    pub fn from_json(json: &str) -> Result<Self> {
+        let file: PoliciesFile =
+            serde_json::from_str(json).context("invalid redaction policy file")?;
+        let mut policies = Self::new(file.default.build().context("default policy")?);
+        for (tenant, spec) in file.tenants {
+            let policy = spec
+                .build()
+                .with_context(|| format!("policy for tenant '{tenant}'"))?;
+            policies = policies.with_tenant(tenant, policy);
+        }
+        Ok(policies)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_json(&json).with_context(|| format!("failed to load {}", path.display()))
+    }
+
+    /// Load `PII_POLICY_FILE` when set, keyed with `PII_HMAC_KEY`; otherwise a
+    /// single default policy from the other `PII_*` variables. A file that
+    /// fails to load is reported and the env policy used instead.
+    pub fn from_env() -> Self {
+        Self::load_env(&mut warn)
+    }
+
+    /// Like [`RedactionPolicies::from_env`], failing on an invalid file or
+    /// the first invalid `PII_*` setting.
+    pub fn try_from_env() -> Result<Self, RedactionConfigError> {
+        first_error(Self::load_env)
+    }
+
+    fn load_env(report: &mut dyn FnMut(RedactionConfigError)) -> Self {
+        let Some(path) = std::env::var_os("PII_POLICY_FILE").filter(|path| !path.is_empty()) else {
+            return Self::new(RedactionPolicy::load_env(report));
+        };
+        match Self::from_file(&path) {
+            Ok(policies) => match std::env::var("PII_HMAC_KEY") {
+                Ok(key) if !key.is_empty() => policies.with_hmac_key(key),
+                _ => policies,
+            },
+            Err(err) => {
+                report(RedactionConfigError::PolicyFile {
+                    path: path.into(),
+                    reason: format!("{err:#}"),
+                });
+                Self::new(RedactionPolicy::load_env(report))
+            }
+        }
+    }
+}
+
+impl From<RedactionPolicy> for RedactionPolicies {
+    fn from(policy: RedactionPolicy) -> Self {
+        Self::new(policy)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PoliciesFile {
+    #[serde(default)]
+    default: PolicySpec,
+    #[serde(default)]
+    tenants: HashMap<String, PolicySpec>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PolicySpec {
+    mode: RedactionMode,
+    allowlist: Vec<String>,
+    strategy: Option<RedactionStrategy>,
+    pattern_strategies: HashMap<String, RedactionStrategy>,
+    patterns: Vec<PatternSpec>,
+    /// In order; the first matching rule wins.
+    fields: Vec<FieldSpec>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternSpec {
+    regex: String,
+    /// Falls back to the policy's default strategy.
+    #[serde(default)]
+    strategy: Option<RedactionStrategy>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FieldSpec {
+    field: String,
+    strategy: RedactionStrategy,
+}
+
+impl PolicySpec {
+    fn build(self) -> Result<RedactionPolicy> {
+        let mut policy = RedactionPolicy::new(self.mode).with_allowlist(self.allowlist);
+        if let Some(strategy) = self.strategy {
+            policy = policy.with_strategy(strategy);
+        }
+        for (name, strategy) in self.pattern_strategies {
+            policy = policy.with_pattern_strategy(&name, strategy)?;
+        }
+        let patterns = self
+            .patterns
+            .into_iter()
+            .map(|pattern| {
+                Ok(Pattern {
+                    name: None,
+                    regex: Regex::new(&pattern.regex)
+                        .with_context(|| format!("invalid pattern '{}'", pattern.regex))?,
+                    strategy: pattern.strategy,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        policy.extend_patterns(patterns);
+        for field in self.fields {
+            policy = policy.with_field(&field.field, field.strategy)?;
+        }
+        Ok(policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"{
+        "default": { "mode": "strict", "fields": [{ "field": "*.password", "strategy": "drop" }] },
+        "tenants": {
+            "clinic": {
+                "mode": "strict",
+                "strategy": "hash",
+                "patterns": [{ "regex": "\\bMRN-\\d+\\b", "strategy": "mask" }]
+            },
+            "internal-test": { "mode": "off" }
+        }
+    }"#;
+
+    #[test]
+    fn tenants_get_their_own_policy() {
+        let policies = RedactionPolicies::from_json(FILE)
+            .unwrap()
+            .with_hmac_key("secret");
+        assert!(policies.is_active());
+
+        let redact = |tenant, value| policies.policy_for(tenant).redact("note", value);
+        assert_eq!(
+            redact(Some("internal-test"), "bob@example.com").as_deref(),
+            Some("bob@example.com")
+        );
+        assert!(
+            redact(Some("clinic"), "bob@example.com")
+                .unwrap()
+                .starts_with("hmac:")
+        );
+        assert_eq!(
+            redact(Some("clinic"), "MRN-1234").as_deref(),
+            Some("[REDACTED]")
+        );
+        assert_eq!(
+            redact(Some("unknown"), "bob@example.com").as_deref(),
+            Some("[REDACTED]")
+        );
+        assert_eq!(redact(None, "MRN-1234").as_deref(), Some("MRN-1234"));
+        assert_eq!(policies.policy_for(None).redact("db.password", "x"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(RedactionPolicies::from_json(r#"{"default": {"mode": "loose"}}"#).is_err());
+        assert!(
+            RedactionPolicies::from_json(r#"{"tenants": {"a": {"strategy": "scramble"}}}"#)
+                .is_err()
+        );
+        assert!(
+            RedactionPolicies::from_json(r#"{"tenants": {"a": {"patterns": [{"regex": "("}]}}}"#)
+                .is_err()
+        );
+        assert!(
+            RedactionPolicies::from_json(
+                r#"{"default": {"pattern_strategies": {"emial": "hash"}}}"#
+            )
+            .is_err()
+        );
+        assert!(RedactionPolicies::from_json(r#"{"defaults": {}}"#).is_err());
+        assert!(!RedactionPolicies::from_json("{}").unwrap().is_active());
+    }
+}
@@ -0,0 +1,11 @@
+//! Sampling strategies that go beyond the SDK's head samplers.
+
+pub mod tail;
+pub mod tenant;
+
+pub use tail::TailSamplingConfig;
+#[cfg(feature = "otlp")]
+pub use tail::TailSamplingProcessor;
+pub use tenant::TenantRateConfig;
+#[cfg(feature = "otlp")]
+pub use tenant::TenantRateSampler;
@@ -0,0 +1,489 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow};
+#[cfg(feature = "otlp")]
+use opentelemetry::{
+    Context as OtelContext,
+    trace::{SpanId, Status, TraceId},
+};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::{
+    Resource,
+    error::OTelSdkResult,
+    trace::{Span, SpanData, SpanProcessor},
+};
+#[cfg(feature = "otlp")]
+use std::collections::{HashMap, VecDeque};
+#[cfg(feature = "otlp")]
+use std::sync::Mutex;
+
+#[cfg(feature = "otlp")]
+use crate::context::context_keys;
+
+/// Rules deciding which complete traces are exported.
+///
+/// A trace is kept when any of its spans has an error status, any span runs
+/// at least `latency_threshold`, or any span carries a `gt.tenant` / `gt.flow`
+/// listed here. Remaining traces are kept with probability `ratio`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TailSamplingConfig {
+    pub keep_errors: bool,
+    pub latency_threshold: Option<Duration>,
+    pub tenants: Vec<String>,
+    pub flows: Vec<String>,
+    pub ratio: f64,
+    /// Traces buffered at once; the oldest is decided early when exceeded.
+    pub max_traces: usize,
+    /// Spans buffered per trace; later spans of a full trace are dropped.
+    pub max_spans_per_trace: usize,
+}
+
+impl Default for TailSamplingConfig {
+    fn default() -> Self {
+        Self {
+            keep_errors: true,
+            latency_threshold: None,
+            tenants: Vec::new(),
+            flows: Vec::new(),
+            ratio: 0.0,
+            max_traces: 10_000,
+            max_spans_per_trace: 1_000,
+        }
+    }
+}
+
+impl TailSamplingConfig {
+    /// Parse a `TELEMETRY_TAIL_SAMPLING` value such as
+    /// `errors,latency_ms=500,tenants=acme|beta,flows=checkout,ratio=0.05`.
+    ///
+    /// Returns `None` for an empty value or `off`.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        let mut config = Self {
+            keep_errors: false,
+            ..Self::default()
+        };
+
+        for entry in value.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (key, val) = entry.split_once('=').unwrap_or((entry, ""));
+            let val = val.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "errors" => {
+                    config.keep_errors = val.is_empty() || parse_bool(val)?;
+                }
+                "latency_ms" => {
+                    let ms: u64 = val
+                        .parse()
+                        .with_context(|| format!("invalid latency_ms value '{val}'"))?;
+                    config.latency_threshold = Some(Duration::from_millis(ms));
+                }
+                "tenants" => config.tenants = parse_list(val),
+                "flows" => config.flows = parse_list(val),
+                "ratio" => {
+                    let ratio: f64 = val
+                        .parse()
+                        .with_context(|| format!("invalid ratio value '{val}'"))?;
+                    if !(0.0..=1.0).contains(&ratio) {
+                        return Err(anyhow!(
+                            "tail sampling ratio must be between 0.0 and 1.0 inclusive, got {ratio}"
+                        ));
+                    }
+                    config.ratio = ratio;
+                }
+                "max_traces" => {
+                    config.max_traces = parse_limit(val, "max_traces")?;
+                }
+                "max_spans" => {
+                    config.max_spans_per_trace = parse_limit(val, "max_spans")?;
+                }
+                other => {
+                    return Err(anyhow!(
+                        "unsupported TELEMETRY_TAIL_SAMPLING key '{other}', expected one of errors, latency_ms, tenants, flows, ratio, max_traces, max_spans"
+                    ));
+                }
+            }
+        }
+
+        Ok(Some(config))
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "on" => Ok(true),
+        "false" | "0" | "off" => Ok(false),
+        other => Err(anyhow!("invalid boolean '{other}'")),
+    }
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_limit(value: &str, key: &str) -> Result<usize> {
+    match value.parse::<usize>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(anyhow!("{key} must be a positive integer, got '{value}'")),
+    }
+}
+
+/// Buffers finished spans per trace and forwards whole traces to `inner` once
+/// the local root span ends and the trace matches the configured rules.
+///
+/// Head sampling should stay permissive (`parent` / always-on) so that the
+/// spans this processor judges are actually recorded.
+#[cfg(feature = "otlp")]
+#[derive(Debug)]
+pub struct TailSamplingProcessor<P> {
+    config: TailSamplingConfig,
+    inner: P,
+    state: Mutex<TailState>,
+}
+
+#[cfg(feature = "otlp")]
+#[derive(Debug, Default)]
+struct TailState {
+    traces: HashMap<TraceId, Vec<SpanData>>,
+    /// Arrival order of buffered traces; may hold ids already decided.
+    order: VecDeque<TraceId>,
+    /// Recent decisions, so spans ending after their root follow the verdict.
+    decided: HashMap<TraceId, bool>,
+    decided_order: VecDeque<TraceId>,
+}
+
+#[cfg(feature = "otlp")]
+impl<P: SpanProcessor> TailSamplingProcessor<P> {
+    pub fn new(config: TailSamplingConfig, inner: P) -> Self {
+        Self {
+            config,
+            inner,
+            state: Mutex::new(TailState::default()),
+        }
+    }
+
+    fn should_keep(&self, trace_id: TraceId, spans: &[SpanData]) -> bool {
+        let config = &self.config;
+
+        if config.keep_errors
+            && spans
+                .iter()
+                .any(|span| matches!(span.status, Status::Error { .. }))
+        {
+            return true;
+        }
+
+        if let Some(threshold) = config.latency_threshold
+            && spans.iter().any(|span| {
+                span.end_time
+                    .duration_since(span.start_time)
+                    .is_ok_and(|elapsed| elapsed >= threshold)
+            })
+        {
+            return true;
+        }
+
+        let matches = |key: &str, wanted: &[String]| {
+            !wanted.is_empty()
+                && spans.iter().any(|span| {
+                    span.attributes.iter().any(|kv| {
+                        kv.key.as_str() == key && wanted.iter().any(|w| *w == kv.value.as_str())
+                    })
+                })
+        };
+        let keys = context_keys();
+        if matches(&keys.tenant, &config.tenants) || matches(&keys.flow, &config.flows) {
+            return true;
+        }
+
+        ratio_keeps(trace_id, config.ratio)
+    }
+
+    /// Decide `trace_id`, remember the verdict and return the spans to export.
+    fn decide(&self, state: &mut TailState, trace_id: TraceId) -> Vec<SpanData> {
+        let spans = state.traces.remove(&trace_id).unwrap_or_default();
+        let keep = self.should_keep(trace_id, &spans);
+
+        state.decided.insert(trace_id, keep);
+        state.decided_order.push_back(trace_id);
+        while state.decided_order.len() > self.config.max_traces {
+            if let Some(old) = state.decided_order.pop_front() {
+                state.decided.remove(&old);
+            }
+        }
+
+        if keep { spans } else { Vec::new() }
+    }
+
+    fn decide_all(&self) -> Vec<SpanData> {
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+        let pending: Vec<TraceId> = state.order.drain(..).collect();
+        let mut export = Vec::new();
+        for trace_id in pending {
+            if state.traces.contains_key(&trace_id) {
+                export.extend(self.decide(&mut state, trace_id));
+            }
+        }
+        export
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl<P: SpanProcessor> SpanProcessor for TailSamplingProcessor<P> {
+    fn on_start(&self, span: &mut Span, cx: &OtelContext) {
+        self.inner.on_start(span, cx);
+    }
+
+    fn on_end(&self, span: SpanData) {
+        let trace_id = span.span_context.trace_id();
+        let is_local_root = span.parent_span_id == SpanId::INVALID || span.parent_span_is_remote;
+
+        let export = {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+
+            if let Some(&keep) = state.decided.get(&trace_id) {
+                if keep {
+                    drop(state);
+                    self.inner.on_end(span);
+                }
+                return;
+            }
+
+            if !state.traces.contains_key(&trace_id) {
+                state.order.push_back(trace_id);
+            }
+            let buffered = state.traces.entry(trace_id).or_default();
+            if buffered.len() < self.config.max_spans_per_trace {
+                buffered.push(span);
+            }
+
+            let mut export = Vec::new();
+            if is_local_root {
+                export.extend(self.decide(&mut state, trace_id));
+            }
+
+            while state.traces.len() > self.config.max_traces {
+                let Some(oldest) = state.order.pop_front() else {
+                    break;
+                };
+                if state.traces.contains_key(&oldest) {
+                    export.extend(self.decide(&mut state, oldest));
+                }
+            }
+
+            if state.order.len() > self.config.max_traces * 2 {
+                let TailState { traces, order, .. } = &mut *state;
+                order.retain(|id| traces.contains_key(id));
+            }
+
+            export
+        };
+
+        for span in export {
+            self.inner.on_end(span);
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        for span in self.decide_all() {
+            self.inner.on_end(span);
+        }
+        self.inner.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        for span in self.decide_all() {
+            self.inner.on_end(span);
+        }
+        self.inner.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.inner.set_resource(resource);
+    }
+}
+
+/// Deterministic per-trace coin flip, matching `TraceIdRatioBased`.
+#[cfg(feature = "otlp")]
+fn ratio_keeps(trace_id: TraceId, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    if ratio <= 0.0 {
+        return false;
+    }
+    let bytes = trace_id.to_bytes();
+    let mut low = [0u8; 8];
+    low.copy_from_slice(&bytes[8..]);
+    let value = u64::from_be_bytes(low) >> 1;
+    value < (ratio * (1u64 << 63) as f64) as u64
+}
+
+#[cfg(all(test, feature = "otlp"))]
+mod tests {
+    use super::*;
+    use opentelemetry::KeyValue;
+    use opentelemetry::trace::{Span as _, TraceContextExt, Tracer, TracerProvider as _};
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use std::sync::Arc;
+
+    #[derive(Debug, Clone, Default)]
+    struct CaptureProcessor {
+        names: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SpanProcessor for CaptureProcessor {
+        fn on_start(&self, _span: &mut Span, _cx: &OtelContext) {}
+
+        fn on_end(&self, span: SpanData) {
+            self.names.lock().unwrap().push(span.name.to_string());
+        }
+
+        fn force_flush(&self) -> OTelSdkResult {
+            Ok(())
+        }
+
+        fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+            Ok(())
+        }
+    }
+
+    fn provider(config: TailSamplingConfig) -> (SdkTracerProvider, Arc<Mutex<Vec<String>>>) {
+        let capture = CaptureProcessor::default();
+        let names = Arc::clone(&capture.names);
+        let provider = SdkTracerProvider::builder()
+            .with_span_processor(TailSamplingProcessor::new(config, capture))
+            .build();
+        (provider, names)
+    }
+
+    fn run_trace(
+        provider: &SdkTracerProvider,
+        root: &str,
+        child_status: Status,
+        attrs: Vec<KeyValue>,
+    ) {
+        let tracer = provider.tracer("tail-test");
+        let mut root_span = tracer.start(root.to_string());
+        root_span.set_attributes(attrs);
+        let cx = OtelContext::current_with_span(root_span);
+        let mut child = tracer.start_with_context("child", &cx);
+        child.set_status(child_status);
+        child.end();
+        cx.span().end();
+    }
+
+    #[test]
+    fn parses_env_value() {
+        let config = TailSamplingConfig::parse(
+            "errors, latency_ms=250, tenants=acme|beta, flows=checkout, ratio=0.1, max_traces=5",
+        )
+        .unwrap()
+        .unwrap();
+        assert!(config.keep_errors);
+        assert_eq!(config.latency_threshold, Some(Duration::from_millis(250)));
+        assert_eq!(config.tenants, vec!["acme", "beta"]);
+        assert_eq!(config.flows, vec!["checkout"]);
+        assert_eq!(config.ratio, 0.1);
+        assert_eq!(config.max_traces, 5);
+
+        assert_eq!(TailSamplingConfig::parse("off").unwrap(), None);
+        assert!(TailSamplingConfig::parse("ratio=2").is_err());
+        assert!(TailSamplingConfig::parse("bogus=1").is_err());
+    }
+
+    #[test]
+    fn keeps_error_traces_and_drops_healthy_ones() {
+        let (provider, names) = provider(TailSamplingConfig::default());
+
+        run_trace(&provider, "healthy", Status::Ok, Vec::new());
+        run_trace(&provider, "failing", Status::error("boom"), Vec::new());
+
+        let names = names.lock().unwrap().clone();
+        assert_eq!(names, vec!["child", "failing"]);
+    }
+
+    #[test]
+    fn keeps_listed_tenants() {
+        let (provider, names) = provider(TailSamplingConfig {
+            tenants: vec!["acme".into()],
+            ..TailSamplingConfig::default()
+        });
+
+        run_trace(
+            &provider,
+            "other",
+            Status::Ok,
+            vec![KeyValue::new("gt.tenant", "zeta")],
+        );
+        run_trace(
+            &provider,
+            "vip",
+            Status::Ok,
+            vec![KeyValue::new("gt.tenant", "acme")],
+        );
+
+        let names = names.lock().unwrap().clone();
+        assert_eq!(names, vec!["child", "vip"]);
+    }
+
+    #[test]
+    fn ratio_one_keeps_everything() {
+        let (provider, names) = provider(TailSamplingConfig {
+            ratio: 1.0,
+            ..TailSamplingConfig::default()
+        });
+
+        run_trace(&provider, "any", Status::Ok, Vec::new());
+        assert_eq!(names.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn evicts_oldest_trace_when_buffer_is_full() {
+        let (provider, names) = provider(TailSamplingConfig {
+            max_traces: 1,
+            ..TailSamplingConfig::default()
+        });
+        let tracer = provider.tracer("tail-test");
+
+        // Two open traces whose children fail; the first is evicted and decided
+        // early when the second arrives.
+        let first = OtelContext::current_with_span(tracer.start("first"));
+        let mut child = tracer.start_with_context("first-child", &first);
+        child.set_status(Status::error("boom"));
+        child.end();
+
+        let second = OtelContext::current_with_span(tracer.start("second"));
+        let mut child = tracer.start_with_context("second-child", &second);
+        child.set_status(Status::error("boom"));
+        child.end();
+
+        assert_eq!(names.lock().unwrap().clone(), vec!["first-child"]);
+
+        // The late root follows the verdict already taken for its trace.
+        first.span().end();
+        assert_eq!(names.lock().unwrap().clone(), vec!["first-child", "first"]);
+
+        provider.force_flush().unwrap();
+        second.span().end();
+        let names = names.lock().unwrap().clone();
+        assert!(names.contains(&"second-child".to_string()));
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, anyhow};
+#[cfg(feature = "otlp")]
+use once_cell::sync::OnceCell;
+#[cfg(feature = "otlp")]
+use opentelemetry::{
+    Context as OtelContext, KeyValue, global,
+    metrics::Counter,
+    trace::{Link, SamplingDecision, SamplingResult, SpanKind, TraceContextExt, TraceId},
+};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::trace::ShouldSample;
+#[cfg(feature = "otlp")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "otlp")]
+use std::time::Instant;
+
+#[cfg(feature = "otlp")]
+use crate::context::context_keys;
+#[cfg(feature = "otlp")]
+use crate::tasklocal::with_current_telemetry_ctx;
+
+/// Buckets that refilled to capacity are indistinguishable from new ones, so
+/// they are pruned once this many tenants are tracked.
+#[cfg(feature = "otlp")]
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// Root-trace budgets, in traces per second, keyed by `gt.tenant`.
+///
+/// Tenants without an override use `default_per_second`; when that is `None`
+/// they are not rate limited. Traces without a tenant are never limited.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TenantRateConfig {
+    pub default_per_second: Option<f64>,
+    pub overrides: HashMap<String, f64>,
+}
+
+impl TenantRateConfig {
+    /// Parse a `TELEMETRY_TENANT_RATE` value such as `default=50,acme=200,noisy=5`.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        let mut config = Self::default();
+        for entry in value.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (tenant, rate) = entry.split_once('=').ok_or_else(|| {
+                anyhow!("invalid TELEMETRY_TENANT_RATE entry '{entry}', expected tenant=rate")
+            })?;
+            let tenant = tenant.trim();
+            let rate: f64 = rate
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid rate for tenant '{tenant}'"))?;
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(anyhow!(
+                    "rate for tenant '{tenant}' must be a non-negative number, got {rate}"
+                ));
+            }
+
+            if tenant.is_empty() {
+                return Err(anyhow!(
+                    "invalid TELEMETRY_TENANT_RATE entry '{entry}', tenant cannot be empty"
+                ));
+            } else if tenant == "default" {
+                config.default_per_second = Some(rate);
+            } else {
+                config.overrides.insert(tenant.to_string(), rate);
+            }
+        }
+
+        Ok(Some(config))
+    }
+
+    #[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+    fn rate_for(&self, tenant: &str) -> Option<f64> {
+        self.overrides
+            .get(tenant)
+            .copied()
+            .or(self.default_per_second)
+    }
+}
+
+/// Head sampler applying a per-tenant token bucket on top of `inner`.
+///
+/// The tenant is read from the `gt.tenant` span attribute, falling back to the
+/// task-local `TelemetryCtx`. Children of local spans follow their parent's
+/// decision, so the budget counts whole traces. Spans continuing a remote
+/// trace go through `inner` and then the budget like roots, so a sampled
+/// `traceparent` does not bypass it. Every shed trace increments the
+/// `greentic.telemetry.traces.sampled_out` counter tagged with `gt.tenant`.
+#[cfg(feature = "otlp")]
+#[derive(Clone, Debug)]
+pub struct TenantRateSampler {
+    config: Arc<TenantRateConfig>,
+    buckets: Arc<Mutex<HashMap<String, TokenBucket>>>,
+    inner: Box<dyn ShouldSample>,
+}
+
+#[cfg(feature = "otlp")]
+impl TenantRateSampler {
+    pub fn new(config: TenantRateConfig, inner: impl ShouldSample + 'static) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            inner: Box::new(inner),
+        }
+    }
+
+    fn try_acquire(&self, tenant: &str) -> bool {
+        let Some(rate) = self.config.rate_for(tenant) else {
+            return true;
+        };
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return true;
+        };
+
+        let now = Instant::now();
+        if buckets.len() >= PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| !bucket.is_full(now));
+        }
+
+        buckets
+            .entry(tenant.to_string())
+            .or_insert_with(|| TokenBucket::new(rate, now))
+            .try_take(now)
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl ShouldSample for TenantRateSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&OtelContext>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        if let Some(parent) = parent_context
+            .filter(|cx| cx.has_active_span())
+            .map(|cx| cx.span().span_context().clone())
+            .filter(|parent| parent.is_valid() && !parent.is_remote())
+        {
+            return SamplingResult {
+                decision: match parent.is_sampled() {
+                    true => SamplingDecision::RecordAndSample,
+                    false => SamplingDecision::Drop,
+                },
+                attributes: Vec::new(),
+                trace_state: parent.trace_state().clone(),
+            };
+        }
+
+        let result =
+            self.inner
+                .should_sample(parent_context, trace_id, name, span_kind, attributes, links);
+        if result.decision == SamplingDecision::Drop {
+            return result;
+        }
+
+        let tenant_key = context_keys().tenant.clone();
+        let tenant = attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == tenant_key)
+            .map(|kv| kv.value.as_str().into_owned())
+            .or_else(|| with_current_telemetry_ctx(|ctx| ctx.map(|ctx| ctx.tenant.clone())))
+            .filter(|tenant| !tenant.is_empty());
+
+        match tenant {
+            Some(tenant) if !self.try_acquire(&tenant) => {
+                sampled_out_counter().add(1, &[KeyValue::new(tenant_key, tenant)]);
+                SamplingResult {
+                    decision: SamplingDecision::Drop,
+                    attributes: Vec::new(),
+                    trace_state: result.trace_state,
+                }
+            }
+            _ => result,
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn sampled_out_counter() -> &'static Counter<u64> {
+    // Created lazily so the counter binds to the meter provider installed
+    // alongside the tracer rather than the no-op default.
+    static COUNTER: OnceCell<Counter<u64>> = OnceCell::new();
+    COUNTER.get_or_init(|| {
+        global::meter("greentic-telemetry")
+            .u64_counter("greentic.telemetry.traces.sampled_out")
+            .with_description("Root traces dropped by the per-tenant rate limit")
+            .build()
+    })
+}
+
+#[cfg(feature = "otlp")]
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+#[cfg(feature = "otlp")]
+impl TokenBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        let capacity = rate.max(1.0);
+        Self {
+            rate,
+            capacity,
+            tokens: if rate > 0.0 { capacity } else { 0.0 },
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.rate > 0.0 && self.tokens + elapsed * self.rate >= self.capacity
+    }
+}
+
+#[cfg(all(test, feature = "otlp"))]
+mod tests {
+    use super::*;
+    use crate::context::TelemetryCtx;
+    use crate::tasklocal::{set_current_telemetry_ctx, with_task_local};
+    use opentelemetry::trace::{
+        Span as _, SpanContext, SpanId, TraceFlags, TraceState, Tracer, TracerProvider as _,
+    };
+    use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+
+    fn sampler(value: &str) -> TenantRateSampler {
+        let config = TenantRateConfig::parse(value).unwrap().unwrap();
+        TenantRateSampler::new(config, Sampler::AlwaysOn)
+    }
+
+    fn sample(sampler: &TenantRateSampler, tenant: Option<&str>) -> SamplingDecision {
+        let attributes: Vec<KeyValue> = tenant
+            .map(|t| vec![KeyValue::new("gt.tenant", t.to_string())])
+            .unwrap_or_default();
+        sampler
+            .should_sample(
+                None,
+                TraceId::from(1u128),
+                "root",
+                &SpanKind::Internal,
+                &attributes,
+                &[],
+            )
+            .decision
+    }
+
+    #[test]
+    fn parses_defaults_and_overrides() {
+        let config = TenantRateConfig::parse("default=50, acme=200,noisy=0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.default_per_second, Some(50.0));
+        assert_eq!(config.overrides.get("acme"), Some(&200.0));
+        assert_eq!(config.rate_for("noisy"), Some(0.0));
+        assert_eq!(config.rate_for("other"), Some(50.0));
+
+        assert!(TenantRateConfig::parse("acme").is_err());
+        assert!(TenantRateConfig::parse("acme=-1").is_err());
+        assert_eq!(TenantRateConfig::parse("").unwrap(), None);
+    }
+
+    #[test]
+    fn budget_applies_per_tenant() {
+        let sampler = sampler("default=2,noisy=0");
+
+        let kept = (0..5)
+            .filter(|_| sample(&sampler, Some("acme")) == SamplingDecision::RecordAndSample)
+            .count();
+        assert_eq!(kept, 2);
+
+        assert_eq!(sample(&sampler, Some("noisy")), SamplingDecision::Drop);
+        assert_eq!(
+            sample(&sampler, Some("beta")),
+            SamplingDecision::RecordAndSample,
+            "other tenants keep their own budget"
+        );
+        assert_eq!(
+            sample(&sampler, None),
+            SamplingDecision::RecordAndSample,
+            "untagged traces are not limited"
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_task_local_tenant_and_children_follow_parent() {
+        let provider = SdkTracerProvider::builder()
+            .with_sampler(sampler("noisy=0"))
+            .build();
+        let tracer = provider.tracer("tenant-test");
+
+        with_task_local(async {
+            set_current_telemetry_ctx(TelemetryCtx::new("noisy"));
+            let root = tracer.start("root");
+            assert!(!root.span_context().is_sampled());
+
+            set_current_telemetry_ctx(TelemetryCtx::new("acme"));
+            let cx = OtelContext::current_with_span(tracer.start("root"));
+            assert!(cx.span().span_context().is_sampled());
+
+            set_current_telemetry_ctx(TelemetryCtx::new("noisy"));
+            let child = tracer.start_with_context("child", &cx);
+            assert!(child.span_context().is_sampled(), "child follows parent");
+        })
+        .await;
+    }
+
+    #[test]
+    fn remote_sampled_parents_take_from_the_budget() {
+        let sampler = TenantRateSampler::new(
+            TenantRateConfig::parse("noisy=0").unwrap().unwrap(),
+            Sampler::ParentBased(Box::new(Sampler::AlwaysOn)),
+        );
+        let remote = OtelContext::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(7u128),
+            SpanId::from(7u64),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ));
+        let decide = |tenant: &str| {
+            sampler
+                .should_sample(
+                    Some(&remote),
+                    TraceId::from(7u128),
+                    "ingress",
+                    &SpanKind::Server,
+                    &[KeyValue::new("gt.tenant", tenant.to_string())],
+                    &[],
+                )
+                .decision
+        };
+        assert_eq!(decide("noisy"), SamplingDecision::Drop);
+        assert_eq!(decide("acme"), SamplingDecision::RecordAndSample);
+    }
+}
@@ -0,0 +1,298 @@
+//! Request rate, error and duration (RED) metrics derived from finished
+//! spans, so flow and node dashboards need no hand-written histograms.
+
+use anyhow::{Result, anyhow};
+#[cfg(feature = "otlp")]
+use globset::{Glob, GlobSet, GlobSetBuilder};
+#[cfg(feature = "otlp")]
+use once_cell::sync::OnceCell;
+#[cfg(feature = "otlp")]
+use opentelemetry::{
+    Context as OtelContext, KeyValue, Value,
+    trace::{SpanKind, Status},
+};
+#[cfg(feature = "otlp")]
+use opentelemetry_sdk::{
+    error::OTelSdkResult,
+    trace::{Span, SpanData, SpanProcessor},
+};
+#[cfg(feature = "otlp")]
+use std::time::Duration;
+
+use crate::context::context_keys;
+#[cfg(feature = "otlp")]
+use crate::metrics::{Counter, Histogram, MetricAttributePolicy, instrument};
+
+/// Finished spans, by span name and kind.
+pub const CALLS: &str = "greentic.span.calls";
+/// Finished spans with an error status.
+pub const ERRORS: &str = "greentic.span.errors";
+/// Span durations, in seconds.
+pub const DURATION: &str = "greentic.span.duration";
+
+/// Which finished spans are counted, and the span attributes that key the
+/// metrics besides `span.name` and `span.kind`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpanMetricsConfig {
+    /// Globs over span names, e.g. `flow.*`; empty matches every span.
+    pub span_names: Vec<String>,
+    /// `tracing` target prefixes, e.g. `greentic_runner`; empty matches
+    /// every target.
+    pub targets: Vec<String>,
+    /// Span attributes copied to the metrics.
+    pub attributes: Vec<String>,
+}
+
+impl Default for SpanMetricsConfig {
+    fn default() -> Self {
+        let keys = context_keys();
+        Self {
+            span_names: Vec::new(),
+            targets: Vec::new(),
+            attributes: ["tenant", "flow", "node"]
+                .map(|name| keys.key(name))
+                .to_vec(),
+        }
+    }
+}
+
+impl SpanMetricsConfig {
+    /// Parse a `TELEMETRY_SPAN_METRICS` value such as `on` or
+    /// `spans=flow.*|node.*,targets=greentic_runner,attributes=gt.flow|gt.node`.
+    ///
+    /// Returns `None` for an empty value or `off`.
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        let value = value.trim();
+        if value.is_empty() || value.eq_ignore_ascii_case("off") {
+            return Ok(None);
+        }
+
+        let mut config = Self::default();
+        for entry in value.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            let (key, val) = entry.split_once('=').unwrap_or((entry, ""));
+            match key.trim().to_ascii_lowercase().as_str() {
+                "on" | "1" | "true" if val.is_empty() => {}
+                "spans" => config.span_names = parse_list(val),
+                "targets" => config.targets = parse_list(val),
+                "attributes" => config.attributes = parse_list(val),
+                other => {
+                    return Err(anyhow!(
+                        "unsupported TELEMETRY_SPAN_METRICS key '{other}', expected one of on, spans, targets, attributes"
+                    ));
+                }
+            }
+        }
+        for glob in &config.span_names {
+            globset::Glob::new(glob)
+                .map_err(|err| anyhow!("invalid span name glob '{glob}': {err}"))?;
+        }
+
+        Ok(Some(config))
+    }
+}
+
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Records [`CALLS`], [`ERRORS`] and [`DURATION`] for every finished span
+/// matching the configured filters.
+///
+/// Register it with [`crate::TelemetryBuilder::with_span_metrics`], or as
+/// one more processor on a tracer provider of your own. It only sees
+/// recorded spans, so head sampling below 100% scales the counts down.
+#[cfg(feature = "otlp")]
+#[derive(Debug)]
+pub struct SpanMetricsProcessor {
+    config: SpanMetricsConfig,
+    span_names: Option<GlobSet>,
+    /// Built on first use, once the meter provider is installed.
+    instruments: OnceCell<Instruments>,
+}
+
+#[cfg(feature = "otlp")]
+#[derive(Debug)]
+struct Instruments {
+    calls: Counter<u64>,
+    errors: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+#[cfg(feature = "otlp")]
+impl Instruments {
+    fn new() -> Self {
+        // Span attributes are passed explicitly; the context of whichever
+        // task ends the span does not apply.
+        let policy = || MetricAttributePolicy::default().with_context_keys(Vec::<String>::new());
+        Self {
+            calls: instrument(CALLS)
+                .with_unit("{span}")
+                .with_description("Finished spans")
+                .with_attribute_policy(policy())
+                .u64_counter(),
+            errors: instrument(ERRORS)
+                .with_unit("{span}")
+                .with_description("Finished spans with an error status")
+                .with_attribute_policy(policy())
+                .u64_counter(),
+            duration: instrument(DURATION)
+                .with_unit("s")
+                .with_description("Span duration")
+                .with_attribute_policy(policy())
+                .f64_histogram(),
+        }
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl SpanMetricsProcessor {
+    pub fn new(config: SpanMetricsConfig) -> Result<Self> {
+        let span_names = if config.span_names.is_empty() {
+            None
+        } else {
+            let mut builder = GlobSetBuilder::new();
+            for glob in &config.span_names {
+                builder.add(
+                    Glob::new(glob)
+                        .map_err(|err| anyhow!("invalid span name glob '{glob}': {err}"))?,
+                );
+            }
+            Some(builder.build()?)
+        };
+        Ok(Self {
+            config,
+            span_names,
+            instruments: OnceCell::new(),
+        })
+    }
+
+    fn matches(&self, span: &SpanData) -> bool {
+        if let Some(names) = &self.span_names
+            && !names.is_match(span.name.as_ref())
+        {
+            return false;
+        }
+        if self.config.targets.is_empty() {
+            return true;
+        }
+        let target = span
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == "target")
+            .map(|kv| kv.value.as_str())
+            .unwrap_or_else(|| span.instrumentation_scope.name().into());
+        self.config
+            .targets
+            .iter()
+            .any(|prefix| target.starts_with(prefix.as_str()))
+    }
+
+    fn attributes(&self, span: &SpanData) -> Vec<KeyValue> {
+        let mut attributes = vec![
+            KeyValue::new("span.name", span.name.clone()),
+            KeyValue::new("span.kind", kind_name(&span.span_kind)),
+        ];
+        let tenant_key = &context_keys().tenant;
+        let tenant = span
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == *tenant_key)
+            .map(|kv| kv.value.as_str());
+        let policy = crate::redaction::policy_for(tenant.as_deref());
+        for key in &self.config.attributes {
+            let Some(kv) = span.attributes.iter().find(|kv| kv.key.as_str() == key) else {
+                continue;
+            };
+            let value = match &kv.value {
+                Value::String(text) => match policy.redact(key, text.as_str()) {
+                    Some(masked) => Value::from(masked.into_owned()),
+                    None => continue,
+                },
+                other => other.clone(),
+            };
+            attributes.push(KeyValue::new(kv.key.clone(), value));
+        }
+        attributes
+    }
+}
+
+#[cfg(feature = "otlp")]
+impl SpanProcessor for SpanMetricsProcessor {
+    fn on_start(&self, _span: &mut Span, _cx: &OtelContext) {}
+
+    fn on_end(&self, span: SpanData) {
+        if !self.matches(&span) {
+            return;
+        }
+        let instruments = self.instruments.get_or_init(Instruments::new);
+        let attributes = self.attributes(&span);
+        let context = &span.span_context;
+
+        instruments.calls.add_in(1, &attributes, context);
+        if matches!(span.status, Status::Error { .. }) {
+            instruments.errors.add_in(1, &attributes, context);
+        }
+        let elapsed = span
+            .end_time
+            .duration_since(span.start_time)
+            .unwrap_or_default();
+        instruments
+            .duration
+            .record_in(elapsed.as_secs_f64(), &attributes, context);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+
+    fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "otlp")]
+fn kind_name(kind: &SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Client => "client",
+        SpanKind::Server => "server",
+        SpanKind::Producer => "producer",
+        SpanKind::Consumer => "consumer",
+        SpanKind::Internal => "internal",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filters_and_attributes() {
+        assert_eq!(SpanMetricsConfig::parse("off").unwrap(), None);
+        assert_eq!(
+            SpanMetricsConfig::parse("on").unwrap(),
+            Some(SpanMetricsConfig::default())
+        );
+
+        let config = SpanMetricsConfig::parse(
+            "spans=flow.*|node.*, targets=greentic_runner, attributes=gt.flow|gt.node",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.span_names, ["flow.*", "node.*"]);
+        assert_eq!(config.targets, ["greentic_runner"]);
+        assert_eq!(config.attributes, ["gt.flow", "gt.node"]);
+
+        assert!(SpanMetricsConfig::parse("spans=flow.[").is_err());
+        assert!(SpanMetricsConfig::parse("names=x").is_err());
+    }
+}
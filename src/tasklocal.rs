@@ -1,37 +1,73 @@
 use crate::context::TelemetryCtx;
-use std::{cell::RefCell, future::Future};
+use pin_project_lite::pin_project;
+use std::{
+    cell::RefCell,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::task::JoinHandle;
+use tracing::Span;
 
 tokio::task_local! {
     static GT_TELEMETRY_CTX: RefCell<Option<TelemetryCtx>>;
 }
 
-/// Set the task-local telemetry context. No-op if outside a Tokio task.
+thread_local! {
+    /// Fallback for synchronous code and threads outside any Tokio task.
+    static THREAD_CTX: RefCell<Option<TelemetryCtx>> = const { RefCell::new(None) };
+}
+
+/// Set the task-local telemetry context, or the thread-local one when called
+/// from plain synchronous code outside any Tokio runtime.
+///
+/// Inside a runtime but outside [`with_task_local`] this is a no-op: worker
+/// threads poll many tasks, so a thread slot would leak the context into
+/// unrelated ones.
 pub fn set_current_telemetry_ctx(ctx: TelemetryCtx) {
+    set_task_or_thread_ctx(Some(ctx));
+}
+
+/// Store or clear the context on the current task, or on the current thread
+/// outside any Tokio runtime; a no-op elsewhere.
+pub(crate) fn set_task_or_thread_ctx(ctx: Option<TelemetryCtx>) {
+    let mut ctx = Some(ctx);
     let _ = GT_TELEMETRY_CTX.try_with(|slot| {
-        *slot.borrow_mut() = Some(ctx);
+        *slot.borrow_mut() = ctx.take().flatten();
     });
+    if let Some(ctx) = ctx
+        && tokio::runtime::Handle::try_current().is_err()
+    {
+        let _ = THREAD_CTX.try_with(|slot| *slot.borrow_mut() = ctx);
+    }
 }
 
-/// Execute `f` with the telemetry context currently stored on this task, if any.
+/// Execute `f` with the telemetry context currently in effect: the task-local
+/// one, then the thread-local one.
+///
+/// The thread-local slot is skipped inside a [`with_task_local`] scope, which
+/// also covers [`WithCtx`] and [`spawn`].
 pub fn with_current_telemetry_ctx<R>(f: impl FnOnce(Option<&TelemetryCtx>) -> R) -> R {
     let mut f = Some(f);
-    let result = GT_TELEMETRY_CTX.try_with(|slot| {
-        let guard = slot.borrow();
+    let mut call = |ctx: Option<&TelemetryCtx>| {
         let func = f
             .take()
             .expect("telemetry context closure already consumed");
-        func(guard.as_ref())
-    });
+        func(ctx)
+    };
 
-    match result {
-        Ok(value) => value,
+    match GT_TELEMETRY_CTX.try_with(|slot| slot.borrow().as_ref().map(|ctx| call(Some(ctx)))) {
+        Ok(Some(value)) => return value,
+        Ok(None) => {}
         Err(_) => {
-            let func = f
-                .take()
-                .expect("telemetry context closure already consumed");
-            func(None)
+            if let Ok(Some(value)) =
+                THREAD_CTX.try_with(|slot| slot.borrow().as_ref().map(|ctx| call(Some(ctx))))
+            {
+                return value;
+            }
         }
     }
+    call(None)
 }
 
 /// Run `fut` with a task-local telemetry context slot initialized.
@@ -41,3 +77,198 @@ where
 {
     GT_TELEMETRY_CTX.scope(RefCell::new(None), fut).await
 }
+
+/// `tokio::spawn` that carries the current context and span into the task.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future.in_current_telemetry_ctx())
+}
+
+/// `tokio::task::spawn_blocking` that exposes the current context through the
+/// thread-local fallback and enters the current span.
+pub fn spawn_blocking<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    tokio::task::spawn_blocking(bind_current_telemetry_ctx(f))
+}
+
+/// Capture the current context and span so `f` sees both wherever it runs,
+/// e.g. `std::thread::spawn(bind_current_telemetry_ctx(work))`.
+pub fn bind_current_telemetry_ctx<F, R>(f: F) -> impl FnOnce() -> R + Send + 'static
+where
+    F: FnOnce() -> R + Send + 'static,
+{
+    let ctx = with_current_telemetry_ctx(|ctx| ctx.cloned());
+    let span = Span::current();
+    move || {
+        let _entered = span.enter();
+        let _restore = ContextGuard::replace_thread(ctx);
+        f()
+    }
+}
+
+/// Restores the context that was current before [`TelemetryCtx::enter`] or
+/// [`bind_current_telemetry_ctx`] replaced it.
+#[must_use = "the previous context is restored when the guard is dropped"]
+#[derive(Debug)]
+pub struct ContextGuard {
+    previous: Option<TelemetryCtx>,
+    slot: Slot,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Task,
+    Thread,
+    /// Nothing was replaced, so nothing is restored.
+    Untouched,
+}
+
+impl ContextGuard {
+    /// Make `ctx` current on this task, or on this thread outside any Tokio
+    /// runtime.
+    ///
+    /// Inside a runtime but outside [`with_task_local`] nothing changes: the
+    /// guard may be held across `.await`, and a worker thread's slot would
+    /// leak `ctx` into the other tasks it polls.
+    pub(crate) fn replace(ctx: Option<TelemetryCtx>) -> Self {
+        let mut ctx = Some(ctx);
+        if let Ok(previous) = GT_TELEMETRY_CTX.try_with(|slot| slot.replace(ctx.take().flatten())) {
+            return Self {
+                previous,
+                slot: Slot::Task,
+            };
+        }
+        if tokio::runtime::Handle::try_current().is_ok() {
+            return Self {
+                previous: None,
+                slot: Slot::Untouched,
+            };
+        }
+        Self::replace_thread(ctx.flatten())
+    }
+
+    /// Make `ctx` current on this task, or on this thread until the guard is
+    /// dropped; the caller must drop it before yielding.
+    #[cfg(feature = "tower")]
+    pub(crate) fn replace_sync(ctx: Option<TelemetryCtx>) -> Self {
+        let mut ctx = Some(ctx);
+        if let Ok(previous) = GT_TELEMETRY_CTX.try_with(|slot| slot.replace(ctx.take().flatten())) {
+            return Self {
+                previous,
+                slot: Slot::Task,
+            };
+        }
+        Self::replace_thread(ctx.flatten())
+    }
+
+    fn replace_thread(ctx: Option<TelemetryCtx>) -> Self {
+        let previous = THREAD_CTX
+            .try_with(|slot| slot.replace(ctx))
+            .unwrap_or_default();
+        Self {
+            previous,
+            slot: Slot::Thread,
+        }
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        match self.slot {
+            Slot::Task => {
+                let _ = GT_TELEMETRY_CTX.try_with(|slot| *slot.borrow_mut() = previous);
+            }
+            Slot::Thread => {
+                let _ = THREAD_CTX.try_with(|slot| *slot.borrow_mut() = previous);
+            }
+            Slot::Untouched => {}
+        }
+    }
+}
+
+/// Layer `child` over the current context.
+pub(crate) fn overlay_current(child: &TelemetryCtx) -> TelemetryCtx {
+    with_current_telemetry_ctx(|parent| match parent {
+        Some(parent) => parent.overlay(child),
+        None => child.clone(),
+    })
+}
+
+/// Run `future` with `child` layered over the current context; the caller's
+/// context is untouched, whatever `future` sets while running.
+pub fn scope<F: Future>(child: TelemetryCtx, future: F) -> WithCtx<F> {
+    future.with_telemetry_ctx(overlay_current(&child))
+}
+
+pin_project! {
+    /// Future or stream polled inside a captured telemetry context and span.
+    ///
+    /// Changes made with [`set_current_telemetry_ctx`] while polling persist
+    /// across polls.
+    #[derive(Debug)]
+    pub struct WithCtx<T> {
+        #[pin]
+        inner: T,
+        ctx: Option<TelemetryCtx>,
+        span: Span,
+    }
+}
+
+impl<T> WithCtx<T> {
+    fn poll_scoped<R>(self: Pin<&mut Self>, poll: impl FnOnce(Pin<&mut T>) -> R) -> R {
+        let this = self.project();
+        let _entered = this.span.enter();
+        let slot = this.ctx;
+        GT_TELEMETRY_CTX.sync_scope(RefCell::new(slot.take()), || {
+            let result = poll(this.inner);
+            *slot = GT_TELEMETRY_CTX.with(|ctx| ctx.borrow_mut().take());
+            result
+        })
+    }
+}
+
+impl<T: Future> Future for WithCtx<T> {
+    type Output = T::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.poll_scoped(|inner| inner.poll(cx))
+    }
+}
+
+impl<T: futures_core::Stream> futures_core::Stream for WithCtx<T> {
+    type Item = T::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_scoped(|inner| inner.poll_next(cx))
+    }
+}
+
+/// Attach a telemetry context to a future or stream.
+pub trait WithTelemetryCtx: Sized {
+    /// Poll with `ctx` as the task-local context, inside the current span.
+    fn with_telemetry_ctx(self, ctx: TelemetryCtx) -> WithCtx<Self> {
+        WithCtx {
+            inner: self,
+            ctx: Some(ctx),
+            span: Span::current(),
+        }
+    }
+
+    /// Poll with the context and span current at the time of the call.
+    fn in_current_telemetry_ctx(self) -> WithCtx<Self> {
+        WithCtx {
+            inner: self,
+            ctx: with_current_telemetry_ctx(|ctx| ctx.cloned()),
+            span: Span::current(),
+        }
+    }
+}
+
+impl<T> WithTelemetryCtx for T {}
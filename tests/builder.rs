@@ -0,0 +1,21 @@
+use greentic_telemetry::{ExportConfig, FmtStyle, TelemetryBuilder};
+use tracing::info;
+
+#[tokio::test]
+async fn builder_installs_once_and_guard_shuts_down() {
+    let guard = TelemetryBuilder::new("builder-test")
+        .with_service_version("0.0.1")
+        .with_deployment_env("test")
+        .with_export_config(ExportConfig::json_default())
+        .with_filter("debug")
+        .with_fmt(FmtStyle::Json)
+        .install()
+        .expect("first install succeeds");
+
+    info!("builder installed");
+
+    let second = TelemetryBuilder::new("builder-test").install();
+    assert!(second.is_err(), "second install must be rejected");
+
+    drop(guard);
+}
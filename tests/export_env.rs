@@ -0,0 +1,39 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::{ExportConfig, ExportMode, Sampling, TelemetryBuilder};
+
+#[tokio::test]
+async fn datadog_preset_over_http_installs() {
+    unsafe {
+        std::env::set_var("CLOUD_PRESET", "datadog");
+        std::env::set_var("DD_API_KEY", "dd-test-key");
+        std::env::set_var("TELEMETRY_EXPORT", "otlp-http");
+        std::env::set_var("OTLP_ENDPOINT", "http://localhost:4318");
+        std::env::set_var("TELEMETRY_SAMPLING", "traceidratio:0.5");
+        std::env::set_var("TELEMETRY_METRICS_EXPORT", "prometheus");
+        std::env::set_var("OTEL_EXPORTER_PROMETHEUS_HOST", "127.0.0.1");
+        std::env::set_var("OTEL_EXPORTER_PROMETHEUS_PORT", "0");
+    }
+
+    let config = ExportConfig::from_env().expect("export config");
+    assert_eq!(config.mode, ExportMode::OtlpHttp);
+    assert_eq!(config.endpoint.as_deref(), Some("http://localhost:4318"));
+    assert_eq!(
+        config.headers.get("DD_API_KEY").map(String::as_str),
+        Some("dd-test-key")
+    );
+    assert_eq!(config.sampling, Sampling::TraceIdRatio(0.5));
+    assert_eq!(config.prometheus.as_deref(), Some("127.0.0.1:0"));
+
+    let guard = TelemetryBuilder::new("export-env-test")
+        .with_export_config(config)
+        .install()
+        .expect("http pipeline installs");
+    assert!(
+        guard.prometheus_addr().is_some(),
+        "metrics are also served for scraping"
+    );
+
+    tracing::info_span!("export-env").in_scope(|| tracing::info!("exported over http"));
+    drop(guard);
+}
@@ -0,0 +1,12 @@
+use greentic_telemetry::{TelemetryConfig, init_telemetry};
+
+#[test]
+fn init_telemetry_keeps_an_existing_global_subscriber() {
+    tracing::subscriber::set_global_default(tracing_subscriber::registry())
+        .expect("first global subscriber");
+
+    init_telemetry(TelemetryConfig {
+        service_name: "existing-subscriber".into(),
+    })
+    .expect("init_telemetry tolerates the application's subscriber");
+}
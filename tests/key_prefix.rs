@@ -0,0 +1,57 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::metrics::MetricAttributePolicy;
+use greentic_telemetry::propagation::BaggagePolicy;
+use greentic_telemetry::sampling::{TenantRateConfig, TenantRateSampler};
+use greentic_telemetry::span_metrics::SpanMetricsConfig;
+use greentic_telemetry::{TelemetryCtx, key_prefix, set_key_prefix};
+use opentelemetry::KeyValue;
+use opentelemetry::trace::{SamplingDecision, SpanKind, TraceId};
+use opentelemetry_sdk::trace::{Sampler, ShouldSample};
+
+#[test]
+fn configured_prefix_drives_every_consumer() {
+    assert!(set_key_prefix("").is_err());
+    assert!(set_key_prefix("acme.").is_err());
+    set_key_prefix("acme").unwrap();
+    assert_eq!(key_prefix(), "acme");
+
+    let ctx = TelemetryCtx::new("t1").with_flow("intake");
+    let keys: Vec<String> = ctx.kv().map(|(key, _)| key.into_owned()).collect();
+    assert_eq!(keys, ["acme.tenant", "acme.flow"]);
+
+    let baggage = BaggagePolicy::default();
+    let header = baggage.encode(&ctx, Some("gt.tenant=other,acme.tenant=stale"));
+    assert_eq!(
+        header.as_deref(),
+        Some("gt.tenant=other,acme.tenant=t1,acme.flow=intake")
+    );
+    let decoded = baggage.decode("gt.tenant=forged,acme.tenant=t2");
+    assert_eq!(decoded.tenant, "t2");
+
+    assert!(MetricAttributePolicy::default().allows("acme.tenant"));
+    assert!(!MetricAttributePolicy::default().allows("gt.tenant"));
+    assert_eq!(
+        SpanMetricsConfig::default().attributes,
+        ["acme.tenant", "acme.flow", "acme.node"]
+    );
+
+    let sampler = TenantRateSampler::new(
+        TenantRateConfig::parse("noisy=0").unwrap().unwrap(),
+        Sampler::AlwaysOn,
+    );
+    let decide = |key: &'static str| {
+        sampler
+            .should_sample(
+                None,
+                TraceId::from(1u128),
+                "root",
+                &SpanKind::Internal,
+                &[KeyValue::new(key, "noisy")],
+                &[],
+            )
+            .decision
+    };
+    assert_eq!(decide("acme.tenant"), SamplingDecision::Drop);
+    assert_eq!(decide("gt.tenant"), SamplingDecision::RecordAndSample);
+}
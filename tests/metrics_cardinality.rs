@@ -0,0 +1,87 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::metrics::{self, MetricAttributePolicy, OVERFLOW_KEY, instrument};
+use greentic_telemetry::{TelemetryCtx, set_current_telemetry_ctx, with_task_local};
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry::{KeyValue, global};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+fn points(metrics: &[ResourceMetrics], name: &str) -> Vec<(u64, Vec<KeyValue>)> {
+    let metric = metrics
+        .iter()
+        .flat_map(|resource| resource.scope_metrics())
+        .flat_map(|scope| scope.metrics())
+        .find(|metric| metric.name() == name)
+        .unwrap_or_else(|| panic!("metric {name} exported"));
+    let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+        panic!("u64 sum, got {:?}", metric.data());
+    };
+    sum.data_points()
+        .map(|point| (point.value(), point.attributes().cloned().collect()))
+        .collect()
+}
+
+#[tokio::test]
+async fn dimensions_are_bounded_and_trace_ids_become_exemplars() {
+    let exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    global::set_meter_provider(provider.clone());
+    let tracer_provider = SdkTracerProvider::builder().build();
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("metrics-test")));
+
+    let calls = instrument("test.tool.calls").u64_counter();
+    let users = instrument("test.tool.users")
+        .with_attribute_policy(MetricAttributePolicy::default().with_cardinality_limit(3))
+        .u64_counter();
+
+    let trace_id = with_task_local(async move {
+        set_current_telemetry_ctx(
+            TelemetryCtx::new("acme")
+                .with_session("s-123")
+                .with_flow("intake")
+                .with_attr("run_id", "r-1"),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("tool.call");
+            let _entered = span.enter();
+            calls.add(1);
+            for user in ["a", "b", "c", "d"] {
+                users.add_with(1, &[KeyValue::new("user", user)]);
+            }
+            span.context().span().span_context().trace_id()
+        })
+    })
+    .await;
+
+    provider.force_flush().unwrap();
+    let metrics = exporter.get_finished_metrics().unwrap();
+
+    let calls = points(&metrics, "test.tool.calls");
+    let keys: Vec<_> = calls[0].1.iter().map(|kv| kv.key.as_str()).collect();
+    assert!(keys.contains(&"gt.tenant"), "{keys:?}");
+    assert!(keys.contains(&"gt.flow"), "{keys:?}");
+    for excluded in ["gt.session", "gt.run_id", "trace_id", "span_id"] {
+        assert!(!keys.contains(&excluded), "{excluded} in {keys:?}");
+    }
+
+    let users = points(&metrics, "test.tool.users");
+    assert_eq!(users.len(), 3, "two series and the overflow one: {users:?}");
+    let overflow = users
+        .iter()
+        .find(|(_, attributes)| attributes.contains(&KeyValue::new(OVERFLOW_KEY, true)))
+        .expect("overflow series");
+    assert_eq!(overflow.0, 2);
+
+    let exemplars = metrics::exemplars("test.tool.calls");
+    assert_eq!(exemplars.len(), 1);
+    assert_eq!(exemplars[0].trace_id, trace_id);
+    assert_eq!(exemplars[0].value, 1.0);
+    assert_eq!(metrics::exemplars("test.tool.users").len(), 3);
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::metrics::instrument;
+use greentic_telemetry::{TelemetryCtx, set_current_telemetry_ctx, with_task_local};
+use opentelemetry::{KeyValue, global};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+fn find<'a>(metrics: &'a [ResourceMetrics], name: &str) -> &'a Metric {
+    metrics
+        .iter()
+        .flat_map(|resource| resource.scope_metrics())
+        .flat_map(|scope| scope.metrics())
+        .find(|metric| metric.name() == name)
+        .unwrap_or_else(|| panic!("metric {name} exported"))
+}
+
+fn has_tenant<'a>(mut attributes: impl Iterator<Item = &'a KeyValue>) -> bool {
+    attributes.any(|kv| kv.key.as_str() == "gt.tenant" && kv.value.as_str() == "acme")
+}
+
+#[tokio::test]
+async fn typed_instruments_export_with_units_and_context() {
+    let exporter = InMemoryMetricExporter::default();
+    let provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    global::set_meter_provider(provider.clone());
+
+    let runs = instrument("test.flow.runs")
+        .with_description("Completed flow runs")
+        .u64_counter();
+    let active = instrument("test.flow.runs.active")
+        .with_unit("{run}")
+        .i64_up_down_counter();
+    let latency = instrument("test.node.latency")
+        .with_unit("ms")
+        .u64_histogram();
+    let _depth = instrument("test.queue.depth")
+        .with_unit("{message}")
+        .u64_observable_gauge(|observer| {
+            observer.observe_with(7, &[KeyValue::new("queue", "intake")]);
+        });
+
+    with_task_local(async {
+        set_current_telemetry_ctx(TelemetryCtx::new("acme"));
+        runs.add(2);
+        active.add(3);
+        active.add(-1);
+        latency.record(12);
+    })
+    .await;
+
+    provider.force_flush().unwrap();
+    let metrics = exporter.get_finished_metrics().unwrap();
+
+    let metric = find(&metrics, "test.flow.runs");
+    assert_eq!(metric.description(), "Completed flow runs");
+    let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+        panic!("u64 sum, got {:?}", metric.data());
+    };
+    assert!(sum.is_monotonic());
+    let point = sum.data_points().next().unwrap();
+    assert_eq!(point.value(), 2);
+    assert!(has_tenant(point.attributes()));
+
+    let metric = find(&metrics, "test.flow.runs.active");
+    assert_eq!(metric.unit(), "{run}");
+    let AggregatedMetrics::I64(MetricData::Sum(sum)) = metric.data() else {
+        panic!("i64 sum, got {:?}", metric.data());
+    };
+    assert!(!sum.is_monotonic());
+    assert_eq!(sum.data_points().next().unwrap().value(), 2);
+
+    let metric = find(&metrics, "test.node.latency");
+    let AggregatedMetrics::U64(MetricData::Histogram(histogram)) = metric.data() else {
+        panic!("u64 histogram, got {:?}", metric.data());
+    };
+    assert_eq!(histogram.data_points().next().unwrap().sum(), 12);
+
+    let metric = find(&metrics, "test.queue.depth");
+    let AggregatedMetrics::U64(MetricData::Gauge(gauge)) = metric.data() else {
+        panic!("u64 gauge, got {:?}", metric.data());
+    };
+    let point = gauge.data_points().next().unwrap();
+    assert_eq!(point.value(), 7);
+    assert!(
+        point
+            .attributes()
+            .any(|kv| kv.key.as_str() == "queue" && kv.value.as_str() == "intake")
+    );
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::metrics::{self, MetricView, instrument};
+use opentelemetry::metrics::MeterProvider as _;
+use opentelemetry::{KeyValue, global};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+fn find<'a>(metrics: &'a [ResourceMetrics], name: &str) -> Option<&'a Metric> {
+    metrics
+        .iter()
+        .flat_map(|resource| resource.scope_metrics())
+        .flat_map(|scope| scope.metrics())
+        .find(|metric| metric.name() == name)
+}
+
+#[test]
+fn views_set_buckets_names_and_attributes() {
+    let exporter = InMemoryMetricExporter::default();
+    let views = [
+        MetricView::new("test.node.*")
+            .unwrap()
+            .with_buckets([1.0, 5.0, 25.0])
+            .with_attributes(["gt.tenant"]),
+        MetricView::new("test.llm.tokens")
+            .unwrap()
+            .with_exponential_buckets(20, 5),
+        MetricView::new("test.legacy.calls")
+            .unwrap()
+            .with_name("test.tool.calls"),
+        MetricView::new("test.deny.*")
+            .unwrap()
+            .without_attributes(["user", "service.name"]),
+    ];
+    let provider = metrics::with_views(
+        SdkMeterProvider::builder().with_reader(PeriodicReader::builder(exporter.clone()).build()),
+        views,
+    )
+    .build();
+    global::set_meter_provider(provider.clone());
+    let meter = provider.meter("views-test");
+
+    let latency = meter.f64_histogram("test.node.duration").build();
+    for value in [0.5, 3.0, 30.0] {
+        latency.record(
+            value,
+            &[
+                KeyValue::new("gt.tenant", "acme"),
+                KeyValue::new("gt.session", "s-1"),
+            ],
+        );
+    }
+    let tokens = meter.u64_histogram("test.llm.tokens").build();
+    tokens.record(1200, &[]);
+    let calls = meter.u64_counter("test.legacy.calls").build();
+    calls.add(1, &[]);
+    let denied = instrument("test.deny.calls").u64_counter();
+    let kept = instrument("test.kept.calls").u64_counter();
+    for user in ["a", "b"] {
+        let attributes = [KeyValue::new("user", user), KeyValue::new("tool", "search")];
+        denied.add_with(1, &attributes);
+        kept.add_with(1, &attributes);
+    }
+
+    provider.force_flush().unwrap();
+    let metrics = exporter.get_finished_metrics().unwrap();
+
+    let metric = find(&metrics, "test.node.duration").expect("latency exported");
+    let AggregatedMetrics::F64(MetricData::Histogram(histogram)) = metric.data() else {
+        panic!("f64 histogram, got {:?}", metric.data());
+    };
+    let point = histogram.data_points().next().unwrap();
+    assert_eq!(point.bounds().collect::<Vec<_>>(), [1.0, 5.0, 25.0]);
+    assert_eq!(point.bucket_counts().collect::<Vec<_>>(), [1, 1, 0, 1]);
+    assert_eq!(
+        point.attributes().cloned().collect::<Vec<_>>(),
+        [KeyValue::new("gt.tenant", "acme")]
+    );
+
+    let metric = find(&metrics, "test.llm.tokens").expect("tokens exported");
+    let AggregatedMetrics::U64(MetricData::ExponentialHistogram(histogram)) = metric.data() else {
+        panic!("u64 exponential histogram, got {:?}", metric.data());
+    };
+    let point = histogram.data_points().next().unwrap();
+    assert_eq!(point.count(), 1);
+    assert!(point.scale() <= 5);
+
+    assert!(find(&metrics, "test.legacy.calls").is_none());
+    assert!(find(&metrics, "test.tool.calls").is_some());
+
+    let points = |name: &str| {
+        let metric = find(&metrics, name).unwrap_or_else(|| panic!("{name} exported"));
+        let AggregatedMetrics::U64(MetricData::Sum(sum)) = metric.data() else {
+            panic!("u64 sum, got {:?}", metric.data());
+        };
+        sum.data_points()
+            .map(|point| (point.value(), point.attributes().cloned().collect()))
+            .collect::<Vec<(u64, Vec<KeyValue>)>>()
+    };
+    assert_eq!(
+        points("test.deny.calls"),
+        [(2, vec![KeyValue::new("tool", "search")])]
+    );
+    assert_eq!(points("test.kept.calls").len(), 2);
+}
+
+#[test]
+fn invalid_views_fail_install() {
+    let err = greentic_telemetry::TelemetryBuilder::new("views-test")
+        .with_metric_view(MetricView::new("test.*").unwrap().with_buckets([10.0, 1.0]))
+        .install()
+        .err()
+        .expect("install fails");
+    assert!(err.to_string().contains("test.*"), "{err}");
+}
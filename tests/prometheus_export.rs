@@ -0,0 +1,149 @@
+#![cfg(feature = "otlp")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+use greentic_telemetry::metrics::instrument;
+use greentic_telemetry::{
+    ExportConfig, TelemetryBuilder, TelemetryCtx, set_current_telemetry_ctx, with_task_local,
+};
+use opentelemetry::trace::TraceContextExt;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+fn get(addr: SocketAddr, path: &str) -> String {
+    get_as(addr, path, "text/plain")
+}
+
+fn get_as(addr: SocketAddr, path: &str, accept: &str) -> String {
+    request(addr, path, accept).expect("metrics endpoint responds")
+}
+
+fn request(addr: SocketAddr, path: &str, accept: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect(addr)?;
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nHost: localhost\r\nAccept: {accept}\r\n\r\n"
+    )?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[tokio::test]
+async fn metrics_are_served_for_scraping() {
+    let guard = TelemetryBuilder::new("prometheus-test")
+        .with_service_version("1.2.3")
+        .with_export_config(ExportConfig::json_default())
+        .with_prometheus("127.0.0.1:0")
+        .install()
+        .expect("prometheus pipeline installs");
+    let addr = guard.prometheus_addr().expect("listening");
+
+    let runs = instrument("test.flow.runs")
+        .with_description("Completed flow runs")
+        .u64_counter();
+    let latency = instrument("test.node.duration")
+        .with_unit("ms")
+        .f64_histogram();
+    let trace_id = with_task_local(async {
+        set_current_telemetry_ctx(TelemetryCtx::new("acme").with_flow("intake"));
+        let span = tracing::info_span!("scrape-test");
+        let _entered = span.enter();
+        runs.add(2);
+        latency.record(12.0);
+        span.context().span().span_context().trace_id()
+    })
+    .await;
+
+    let response = get(addr, "/metrics");
+    let (head, body) = response.split_once("\r\n\r\n").expect("http response");
+    assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+    assert!(
+        head.contains("Content-Type: text/plain; version=0.0.4"),
+        "{head}"
+    );
+
+    let target_info = body
+        .lines()
+        .find(|line| line.starts_with("target_info{"))
+        .unwrap_or_else(|| panic!("target_info in {body}"));
+    assert!(
+        target_info.contains("service_name=\"prometheus-test\""),
+        "{target_info}"
+    );
+    assert!(
+        target_info.contains("service_version=\"1.2.3\""),
+        "{target_info}"
+    );
+
+    assert!(
+        body.contains("# HELP test_flow_runs_total Completed flow runs\n"),
+        "{body}"
+    );
+    assert!(
+        body.contains("# TYPE test_flow_runs_total counter\n"),
+        "{body}"
+    );
+    let runs = body
+        .lines()
+        .find(|line| line.starts_with("test_flow_runs_total{"))
+        .unwrap_or_else(|| panic!("counter sample in {body}"));
+    assert!(runs.contains("gt_tenant=\"acme\""), "{runs}");
+    assert!(runs.contains("gt_flow=\"intake\""), "{runs}");
+    assert!(runs.ends_with(" 2"), "{runs}");
+    assert!(
+        body.contains("# TYPE test_node_duration_milliseconds histogram\n"),
+        "{body}"
+    );
+    assert!(
+        body.contains("test_node_duration_milliseconds_count{"),
+        "{body}"
+    );
+
+    assert!(
+        !body.contains(" # {"),
+        "no exemplars in the text format: {body}"
+    );
+
+    let response = get_as(
+        addr,
+        "/metrics",
+        "application/openmetrics-text;version=1.0.0,text/plain;version=0.0.4;q=0.5",
+    );
+    let (head, body) = response.split_once("\r\n\r\n").expect("http response");
+    assert!(
+        head.contains("Content-Type: application/openmetrics-text; version=1.0.0"),
+        "{head}"
+    );
+    assert!(body.contains("# TYPE test_flow_runs counter\n"), "{body}");
+    assert!(body.ends_with("# EOF\n"), "{body}");
+    let linked = format!("# {{trace_id=\"{trace_id}\",span_id=\"");
+    let runs = body
+        .lines()
+        .find(|line| line.starts_with("test_flow_runs_total{"))
+        .unwrap_or_else(|| panic!("counter sample in {body}"));
+    assert!(runs.contains(&linked), "{runs}");
+    let buckets: Vec<_> = body
+        .lines()
+        .filter(|line| line.starts_with("test_node_duration_milliseconds_bucket{"))
+        .filter(|line| line.contains(&linked))
+        .collect();
+    assert_eq!(buckets.len(), 1, "one bucket holds the exemplar: {body}");
+    assert!(buckets[0].contains("le=\"25\""), "{}", buckets[0]);
+
+    assert!(get(addr, "/").starts_with("HTTP/1.1 404"));
+    // A client that never sends its request holds up no other scrape, and
+    // clients past the limit are turned away instead of tying up threads.
+    let idle: Vec<_> = (0..4).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    let mut refused = TcpStream::connect(addr).unwrap();
+    let mut buf = [0; 1];
+    assert!(matches!(refused.read(&mut buf), Ok(0) | Err(_)), "closed");
+    drop(idle);
+    let served = (0..50).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        request(addr, "/metrics", "text/plain")
+            .is_ok_and(|response| response.starts_with("HTTP/1.1 200"))
+    });
+    assert!(served, "slots are released when clients go away");
+    drop(guard);
+}
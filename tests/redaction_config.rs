@@ -0,0 +1,99 @@
+use greentic_telemetry::TelemetryBuilder;
+use greentic_telemetry::redaction::{
+    self, RedactionConfigError, RedactionMode, RedactionPolicy, RedactionStrategy,
+    validate_redaction_config,
+};
+
+fn set(vars: &[(&str, &str)]) {
+    for var in [
+        "PII_REDACTION_MODE",
+        "PII_MASK_REGEXES",
+        "PII_FIELD_STRATEGIES",
+        "PII_PATTERN_STRATEGIES",
+        "PII_POLICY_FILE",
+    ] {
+        unsafe { std::env::remove_var(var) };
+    }
+    for (key, value) in vars {
+        unsafe { std::env::set_var(key, value) };
+    }
+}
+
+#[test]
+fn invalid_config_is_rejected_in_strict_mode() {
+    set(&[("PII_REDACTION_MODE", "stritc")]);
+    assert!(matches!(
+        validate_redaction_config(),
+        Err(RedactionConfigError::UnknownMode { value, .. }) if value == "stritc"
+    ));
+    assert_eq!(RedactionPolicy::from_env().mode(), RedactionMode::Off);
+
+    set(&[
+        ("PII_REDACTION_MODE", "strict"),
+        ("PII_MASK_REGEXES", r"\bacct-\d+,(unclosed"),
+    ]);
+    assert!(matches!(
+        RedactionPolicy::try_from_env(),
+        Err(RedactionConfigError::InvalidRegex { pattern, .. }) if pattern == "(unclosed"
+    ));
+    let lenient = RedactionPolicy::from_env();
+    assert_eq!(
+        lenient.redact("note", "acct-42").as_deref(),
+        Some("[REDACTED]")
+    );
+
+    set(&[
+        ("PII_REDACTION_MODE", "strict"),
+        ("PII_FIELD_STRATEGIES", "password=scramble"),
+    ]);
+    let err = validate_redaction_config().unwrap_err();
+    assert!(matches!(
+        err,
+        RedactionConfigError::InvalidEntry {
+            var: "PII_FIELD_STRATEGIES",
+            ..
+        }
+    ));
+
+    set(&[
+        ("PII_REDACTION_MODE", "strict"),
+        ("PII_PATTERN_STRATEGIES", "emial=hash"),
+    ]);
+    assert!(matches!(
+        validate_redaction_config(),
+        Err(RedactionConfigError::InvalidEntry {
+            var: "PII_PATTERN_STRATEGIES",
+            entry,
+            ..
+        }) if entry == "emial"
+    ));
+    assert!(
+        RedactionPolicy::new(RedactionMode::Strict)
+            .with_pattern_strategy("emial", RedactionStrategy::Hash)
+            .is_err()
+    );
+
+    set(&[
+        ("PII_REDACTION_MODE", "strict"),
+        ("PII_POLICY_FILE", "/nonexistent/redaction.json"),
+    ]);
+    assert!(matches!(
+        redaction::try_init_from_env(),
+        Err(RedactionConfigError::PolicyFile { .. })
+    ));
+
+    let err = TelemetryBuilder::new("strict-redaction")
+        .with_strict_redaction(true)
+        .install()
+        .err()
+        .expect("install refuses invalid redaction config");
+    assert!(
+        err.downcast_ref::<RedactionConfigError>().is_some(),
+        "{err:#}"
+    );
+
+    set(&[("PII_REDACTION_MODE", "strict")]);
+    validate_redaction_config().unwrap();
+    assert!(redaction::try_init_from_env().unwrap());
+    assert!(redaction::is_active());
+}
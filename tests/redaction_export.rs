@@ -0,0 +1,206 @@
+#![cfg(feature = "otlp")]
+
+use std::io::Write;
+use std::sync::{Arc, Mutex, Once};
+
+use greentic_telemetry::redaction::{
+    self, RedactingFields, RedactingFormat, RedactingSpanProcessor, RedactionLogProcessor,
+};
+use opentelemetry::logs::AnyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{InstrumentationScope, Value};
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::logs::{LogProcessor, SdkLogRecord, SdkLoggerProvider};
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor};
+use tracing_subscriber::fmt::format::DefaultFields;
+use tracing_subscriber::{Registry, fmt, layer::SubscriberExt};
+
+fn strict() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        unsafe {
+            std::env::set_var("PII_REDACTION_MODE", "strict");
+            std::env::set_var("PII_FIELD_STRATEGIES", "password=drop,*.password=drop");
+        }
+        redaction::init_from_env();
+    });
+}
+
+#[derive(Debug, Default, Clone)]
+struct CaptureLogs(Arc<Mutex<Vec<SdkLogRecord>>>);
+
+impl LogProcessor for CaptureLogs {
+    fn emit(&self, record: &mut SdkLogRecord, _scope: &InstrumentationScope) {
+        self.0.lock().unwrap().push(record.clone());
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn span_attributes_and_events_are_masked_before_export() {
+    strict();
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_span_processor(RedactingSpanProcessor::new(SimpleSpanProcessor::new(
+            exporter.clone(),
+        )))
+        .build();
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("redaction-test")));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "signup",
+            email = "alice@example.com",
+            password = "hunter2",
+            step = 2,
+            payload = r#"{"user":{"email":"alice@example.com","password":"hunter2","plan":"pro"}}"#
+        );
+        let _entered = span.enter();
+        tracing::warn!(auth = "Bearer abc123", "mailing bob@example.com");
+    });
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let span = &spans[0];
+    let attr = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(attr("email"), Some(Value::from("[REDACTED]")));
+    assert_eq!(attr("step"), Some(Value::I64(2)));
+    assert_eq!(attr("password"), None, "dropped by field rule");
+    let payload: serde_json::Value = serde_json::from_str(
+        attr("payload")
+            .expect("payload attribute")
+            .as_str()
+            .as_ref(),
+    )
+    .unwrap();
+    assert_eq!(
+        payload,
+        serde_json::json!({"user": {"email": "[REDACTED]", "plan": "pro"}})
+    );
+
+    let event = &span.events.events[0];
+    assert_eq!(event.name, "mailing [REDACTED]");
+    let auth = event
+        .attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == "auth")
+        .expect("auth attribute");
+    assert_eq!(auth.value.as_str(), "[REDACTED]");
+}
+
+#[test]
+fn log_records_are_masked_before_export() {
+    strict();
+    let capture = CaptureLogs::default();
+    let provider = SdkLoggerProvider::builder()
+        .with_log_processor(RedactionLogProcessor)
+        .with_log_processor(capture.clone())
+        .build();
+    let subscriber = Registry::default().with(OpenTelemetryTracingBridge::new(&provider));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::warn!(
+            user = "carol@example.com",
+            password = "hunter2",
+            attempt = 3,
+            "call +12345678901"
+        );
+    });
+
+    let records = capture.0.lock().unwrap();
+    let record = &records[0];
+    assert_eq!(record.body(), Some(&AnyValue::from("call [REDACTED]")));
+    assert_eq!(record.target().map(|t| t.as_ref()), Some(module_path!()));
+    let attr = |key: &str| {
+        record
+            .attributes_iter()
+            .find(|(k, _)| k.as_str() == key)
+            .map(|(_, v)| v.clone())
+    };
+    assert_eq!(attr("user"), Some(AnyValue::from("[REDACTED]")));
+    assert_eq!(attr("attempt"), Some(AnyValue::Int(3)));
+    assert_eq!(attr("password"), None);
+}
+
+#[test]
+fn fmt_text_and_json_output_is_masked() {
+    strict();
+    let text = Buffer::default();
+    let json = Buffer::default();
+    let (text_writer, json_writer) = (text.clone(), json.clone());
+    let subscriber = Registry::default()
+        .with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || text_writer.clone())
+                .fmt_fields(RedactingFields::new(DefaultFields::new())),
+        )
+        .with(
+            fmt::layer()
+                .json()
+                .with_span_list(true)
+                .with_writer(move || json_writer.clone())
+                .map_event_format(RedactingFormat::new),
+        );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("checkout", customer = "dave@example.com");
+        let _entered = span.enter();
+        tracing::info!(
+            card = "token=sk_live_123",
+            "receipt sent to dave@example.com"
+        );
+    });
+
+    for output in [text.contents(), json.contents()] {
+        assert!(!output.is_empty());
+        assert!(!output.contains("dave@example.com"), "{output}");
+        assert!(!output.contains("sk_live_123"), "{output}");
+        assert!(output.contains("[REDACTED]"), "{output}");
+        assert!(!output.contains("hunter2"), "{output}");
+    }
+    let line: serde_json::Value = serde_json::from_str(json.contents().trim()).unwrap();
+    assert_eq!(line["fields"]["message"], "receipt sent to [REDACTED]");
+    assert_eq!(line["spans"][0]["customer"], "[REDACTED]");
+    assert_eq!(line["spans"][0]["name"], "checkout");
+    let keys: Vec<&str> = line
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(
+        keys,
+        ["timestamp", "level", "fields", "target", "span", "spans"],
+        "the formatter's key order is kept"
+    );
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::redaction::{
+    self, RedactingSpanProcessor, RedactionMode, RedactionPolicies, RedactionPolicy,
+};
+use greentic_telemetry::{TelemetryCtx, set_current_telemetry_ctx, with_task_local};
+use opentelemetry::trace::{Span as _, Tracer as _, TracerProvider as _};
+use opentelemetry::{KeyValue, Value};
+use opentelemetry_sdk::trace::{
+    InMemorySpanExporter, SdkTracerProvider, SimpleSpanProcessor, SpanData,
+};
+
+fn email_of(span: &SpanData) -> Value {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == "email")
+        .map(|kv| kv.value.clone())
+        .expect("email attribute")
+}
+
+#[tokio::test]
+async fn policies_follow_the_tenant_and_can_be_swapped() {
+    redaction::set_policies(
+        RedactionPolicies::new(RedactionPolicy::new(RedactionMode::Strict))
+            .with_tenant("internal-test", RedactionPolicy::new(RedactionMode::Off)),
+    );
+
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_span_processor(RedactingSpanProcessor::new(SimpleSpanProcessor::new(
+            exporter.clone(),
+        )))
+        .build();
+    let tracer = provider.tracer("tenant-redaction");
+    let emit = |tenant: Option<&str>| {
+        let mut span = tracer.start("signup");
+        if let Some(tenant) = tenant {
+            span.set_attribute(KeyValue::new("gt.tenant", tenant.to_string()));
+        }
+        span.set_attribute(KeyValue::new("email", "alice@example.com"));
+        span.end();
+    };
+
+    emit(Some("hipaa"));
+    emit(Some("internal-test"));
+    with_task_local(async {
+        set_current_telemetry_ctx(TelemetryCtx::new("internal-test"));
+        emit(None);
+    })
+    .await;
+
+    let dir = std::env::temp_dir().join(format!("redaction-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("policies.json");
+    std::fs::write(
+        &file,
+        r#"{"default": {"mode": "off"}, "tenants": {"hipaa": {"mode": "strict", "strategy": "preserve"}}}"#,
+    )
+    .unwrap();
+    redaction::reload_from_file(&file).unwrap();
+    emit(Some("hipaa"));
+    emit(Some("internal-test"));
+
+    std::fs::write(&file, r#"{"default": {"mode": "bogus"}}"#).unwrap();
+    assert!(redaction::reload_from_file(&file).is_err());
+    emit(Some("hipaa"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let emails: Vec<String> = spans
+        .iter()
+        .map(|span| email_of(span).to_string())
+        .collect();
+    assert_eq!(
+        emails,
+        [
+            "[REDACTED]",
+            "alice@example.com",
+            "alice@example.com",
+            "a****@example.com",
+            "alice@example.com",
+            "a****@example.com",
+        ]
+    );
+}
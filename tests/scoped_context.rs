@@ -0,0 +1,107 @@
+use greentic_telemetry::{
+    TelemetryCtx, scope, set_current_telemetry_ctx, with_current_telemetry_ctx, with_task_local,
+};
+
+fn current() -> Option<TelemetryCtx> {
+    with_current_telemetry_ctx(|ctx| ctx.cloned())
+}
+
+#[tokio::test]
+async fn nested_guards_override_and_restore() {
+    with_task_local(async {
+        set_current_telemetry_ctx(
+            TelemetryCtx::new("acme")
+                .with_flow("intake")
+                .with_node("n1"),
+        );
+
+        {
+            let _node = TelemetryCtx::default().with_node("n2").enter();
+            tokio::task::yield_now().await;
+            let ctx = current().unwrap();
+            assert_eq!(ctx.tenant, "acme");
+            assert_eq!(ctx.flow.as_deref(), Some("intake"));
+            assert_eq!(ctx.node.as_deref(), Some("n2"));
+
+            {
+                let _sub_flow = TelemetryCtx::default()
+                    .with_flow("billing")
+                    .with_attr("run_id", "r-7")
+                    .enter();
+                let ctx = current().unwrap();
+                assert_eq!(ctx.flow.as_deref(), Some("billing"));
+                assert_eq!(ctx.node.as_deref(), Some("n2"));
+            }
+
+            let ctx = current().unwrap();
+            assert_eq!(ctx.flow.as_deref(), Some("intake"));
+            assert!(ctx.attr("run_id").is_none());
+        }
+
+        assert_eq!(current().unwrap().node.as_deref(), Some("n1"));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn scope_layers_child_for_the_future_only() {
+    with_task_local(async {
+        set_current_telemetry_ctx(TelemetryCtx::new("acme").with_node("n1"));
+
+        let node = scope(TelemetryCtx::default().with_node("n2"), async {
+            tokio::task::yield_now().await;
+            set_current_telemetry_ctx(TelemetryCtx::new("other"));
+            current().unwrap().tenant
+        })
+        .await;
+        assert_eq!(node, "other");
+
+        let ctx = current().unwrap();
+        assert_eq!(ctx.tenant, "acme");
+        assert_eq!(ctx.node.as_deref(), Some("n1"));
+
+        let inherited = scope(TelemetryCtx::default().with_node("n3"), async {
+            current().unwrap()
+        })
+        .await;
+        assert_eq!(inherited.tenant, "acme");
+        assert_eq!(inherited.node.as_deref(), Some("n3"));
+    })
+    .await;
+}
+
+#[test]
+fn guard_uses_thread_local_outside_tasks() {
+    let _root = TelemetryCtx::new("sync").enter();
+    {
+        let _child = TelemetryCtx::default().with_flow("f").enter();
+        assert_eq!(current().unwrap().flow.as_deref(), Some("f"));
+    }
+    let ctx = current().unwrap();
+    assert_eq!(ctx.tenant, "sync");
+    assert_eq!(ctx.flow, None);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn guard_outside_task_local_leaks_into_no_other_task() {
+    let held = tokio::spawn(async {
+        let _guard = TelemetryCtx::new("leaky").enter();
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+        current()
+    });
+    let others: Vec<_> = (0..20)
+        .map(|_| {
+            tokio::spawn(async {
+                tokio::task::yield_now().await;
+                current()
+            })
+        })
+        .collect();
+
+    assert_eq!(held.await.unwrap(), None, "the guard is a no-op here");
+    for other in others {
+        assert_eq!(other.await.unwrap(), None);
+    }
+}
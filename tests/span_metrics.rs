@@ -0,0 +1,124 @@
+#![cfg(feature = "otlp")]
+
+use greentic_telemetry::metrics;
+use greentic_telemetry::span_metrics::{
+    CALLS, DURATION, ERRORS, SpanMetricsConfig, SpanMetricsProcessor,
+};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{KeyValue, global};
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+fn find<'a>(metrics: &'a [ResourceMetrics], name: &str) -> &'a Metric {
+    metrics
+        .iter()
+        .flat_map(|resource| resource.scope_metrics())
+        .flat_map(|scope| scope.metrics())
+        .find(|metric| metric.name() == name)
+        .unwrap_or_else(|| panic!("metric {name} exported"))
+}
+
+fn attr<'a>(mut attributes: impl Iterator<Item = &'a KeyValue>, key: &str) -> Option<String> {
+    attributes
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.as_str().into_owned())
+}
+
+#[test]
+fn finished_spans_record_red_metrics() {
+    let exporter = InMemoryMetricExporter::default();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter.clone()).build())
+        .build();
+    global::set_meter_provider(meter_provider.clone());
+
+    let config = SpanMetricsConfig {
+        span_names: vec!["node.*".into()],
+        ..SpanMetricsConfig::default()
+    };
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_span_processor(SpanMetricsProcessor::new(config).unwrap())
+        .build();
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("red-test")));
+
+    tracing::subscriber::with_default(subscriber, || {
+        for failed in [false, false, true] {
+            let span = tracing::info_span!(
+                "node.run",
+                otel.kind = "server",
+                otel.status_code = tracing::field::Empty,
+                gt.tenant = "acme",
+                gt.flow = "intake",
+                gt.node = "parse",
+                gt.session = "s-1",
+            );
+            if failed {
+                span.record("otel.status_code", "ERROR");
+            }
+            span.in_scope(|| {});
+        }
+        tracing::info_span!("db.query").in_scope(|| {});
+    });
+
+    meter_provider.force_flush().unwrap();
+    let exported = exporter.get_finished_metrics().unwrap();
+
+    let AggregatedMetrics::U64(MetricData::Sum(calls)) = find(&exported, CALLS).data() else {
+        panic!("u64 sum");
+    };
+    let points: Vec<_> = calls.data_points().collect();
+    assert_eq!(points.len(), 1, "db.query is filtered out");
+    let point = points[0];
+    assert_eq!(point.value(), 3);
+    assert_eq!(
+        attr(point.attributes(), "span.name").as_deref(),
+        Some("node.run")
+    );
+    assert_eq!(
+        attr(point.attributes(), "span.kind").as_deref(),
+        Some("server")
+    );
+    assert_eq!(
+        attr(point.attributes(), "gt.flow").as_deref(),
+        Some("intake")
+    );
+    assert_eq!(
+        attr(point.attributes(), "gt.node").as_deref(),
+        Some("parse")
+    );
+    assert_eq!(attr(point.attributes(), "gt.session"), None);
+
+    let AggregatedMetrics::U64(MetricData::Sum(errors)) = find(&exported, ERRORS).data() else {
+        panic!("u64 sum");
+    };
+    assert_eq!(errors.data_points().next().unwrap().value(), 1);
+
+    let duration = find(&exported, DURATION);
+    assert_eq!(duration.unit(), "s");
+    let AggregatedMetrics::F64(MetricData::Histogram(histogram)) = duration.data() else {
+        panic!("f64 histogram");
+    };
+    assert_eq!(histogram.data_points().next().unwrap().count(), 3);
+
+    let exemplars = metrics::exemplars(CALLS);
+    assert_eq!(exemplars.len(), 1, "linked to the ended span");
+}
+
+#[test]
+fn span_metrics_are_configured_from_env() {
+    unsafe {
+        std::env::set_var("TELEMETRY_SPAN_METRICS", "spans=flow.*,attributes=gt.flow");
+    }
+    let config = greentic_telemetry::ExportConfig::from_env().unwrap();
+    assert_eq!(
+        config.span_metrics,
+        Some(SpanMetricsConfig {
+            span_names: vec!["flow.*".into()],
+            targets: Vec::new(),
+            attributes: vec!["gt.flow".into()],
+        })
+    );
+}
@@ -0,0 +1,99 @@
+use greentic_telemetry::{
+    TelemetryCtx, WithTelemetryCtx, bind_current_telemetry_ctx, set_current_telemetry_ctx, spawn,
+    spawn_blocking, with_current_telemetry_ctx, with_task_local,
+};
+use tokio_stream::StreamExt;
+use tracing_subscriber::Registry;
+
+fn current_tenant() -> Option<String> {
+    with_current_telemetry_ctx(|ctx| ctx.map(|ctx| ctx.tenant.clone()))
+}
+
+fn current_span_name() -> Option<&'static str> {
+    tracing::Span::current().metadata().map(|meta| meta.name())
+}
+
+#[tokio::test]
+async fn spawned_work_inherits_context_and_span() {
+    let _subscriber = tracing::subscriber::set_default(Registry::default());
+
+    with_task_local(async {
+        set_current_telemetry_ctx(TelemetryCtx::new("acme").with_flow("intake"));
+        let span = tracing::info_span!("parent");
+        let _entered = span.enter();
+
+        let task = spawn(async { (current_tenant(), current_span_name()) });
+        assert_eq!(
+            task.await.unwrap(),
+            (Some("acme".to_string()), Some("parent"))
+        );
+
+        let blocking = spawn_blocking(current_tenant);
+        assert_eq!(blocking.await.unwrap().as_deref(), Some("acme"));
+
+        let thread = std::thread::spawn(bind_current_telemetry_ctx(current_tenant));
+        assert_eq!(thread.join().unwrap().as_deref(), Some("acme"));
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn futures_and_streams_carry_explicit_context() {
+    let fut = async {
+        let before = current_tenant();
+        set_current_telemetry_ctx(TelemetryCtx::new("beta"));
+        tokio::task::yield_now().await;
+        (before, current_tenant())
+    };
+    assert_eq!(
+        fut.with_telemetry_ctx(TelemetryCtx::new("acme")).await,
+        (Some("acme".to_string()), Some("beta".to_string()))
+    );
+    assert_eq!(current_tenant(), None, "context does not leak out");
+
+    let tenants: Vec<_> = tokio_stream::iter(0..2)
+        .map(|_| current_tenant())
+        .with_telemetry_ctx(TelemetryCtx::new("acme"))
+        .collect()
+        .await;
+    assert_eq!(
+        tenants,
+        [Some("acme".to_string()), Some("acme".to_string())]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn concurrent_tasks_never_see_each_others_context() {
+    let tasks: Vec<_> = ["acme", "beta"]
+        .into_iter()
+        .map(|tenant| {
+            tokio::spawn(async move {
+                for _ in 0..100 {
+                    set_current_telemetry_ctx(TelemetryCtx::new(tenant));
+                    tokio::task::yield_now().await;
+                    assert_eq!(current_tenant(), None, "{tenant} task outside a scope");
+                }
+                with_task_local(async move {
+                    for _ in 0..100 {
+                        set_current_telemetry_ctx(TelemetryCtx::new(tenant));
+                        tokio::task::yield_now().await;
+                        assert_eq!(current_tenant().as_deref(), Some(tenant));
+                    }
+                })
+                .await;
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+#[test]
+fn thread_local_fallback_outside_tasks() {
+    set_current_telemetry_ctx(TelemetryCtx::new("sync"));
+    assert_eq!(current_tenant().as_deref(), Some("sync"));
+
+    let other = std::thread::spawn(current_tenant).join().unwrap();
+    assert_eq!(other, None, "plain threads start without context");
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "tower")]
+
+use std::convert::Infallible;
+
+use greentic_telemetry::{
+    TelemetryCtx, TelemetryLayer, set_current_telemetry_ctx, with_current_telemetry_ctx,
+    with_task_local,
+};
+use http::{Request, Response, StatusCode};
+use opentelemetry::trace::{SpanKind, Status, TracerProvider as _};
+use opentelemetry::{Value, global};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use tower::{Layer, ServiceExt, service_fn};
+use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.clone())
+}
+
+#[tokio::test]
+async fn client_and_server_spans_share_trace_and_context() {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("tower-test")));
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let handler = service_fn(|req: Request<String>| async move {
+        let tenant = with_current_telemetry_ctx(|ctx| ctx.map(|ctx| ctx.tenant.clone()));
+        assert_eq!(tenant.as_deref(), Some("acme"), "context from baggage");
+        assert!(req.headers().contains_key("traceparent"));
+        Ok::<_, Infallible>(
+            Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(String::new())
+                .unwrap(),
+        )
+    });
+    let server = TelemetryLayer::server().layer(handler);
+    // Stand-in for the network: the server runs without the caller's context.
+    let transport = service_fn(move |req: Request<String>| {
+        let server = server.clone();
+        async move { tokio::spawn(server.oneshot(req)).await.unwrap() }
+    });
+    let client = TelemetryLayer::client().layer(transport);
+
+    let response = with_task_local(async {
+        set_current_telemetry_ctx(TelemetryCtx::new("acme"));
+        let request = Request::get("http://orders.internal:8080/v1/orders?page=2")
+            .header("user-agent", "greentic-test")
+            .header("host", "orders.example:8443")
+            .body(String::new())
+            .unwrap();
+        client.oneshot(request).await.unwrap()
+    })
+    .await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let server = spans
+        .iter()
+        .find(|span| span.span_kind == SpanKind::Server)
+        .expect("server span");
+    let client = spans
+        .iter()
+        .find(|span| span.span_kind == SpanKind::Client)
+        .expect("client span");
+
+    assert_eq!(client.name, "GET");
+    assert_eq!(server.parent_span_id, client.span_context.span_id());
+    assert_eq!(
+        server.span_context.trace_id(),
+        client.span_context.trace_id()
+    );
+
+    assert_eq!(
+        attribute(server, "http.response.status_code"),
+        Some(Value::I64(503))
+    );
+    assert_eq!(attribute(server, "url.path"), Some("/v1/orders".into()));
+    assert_eq!(attribute(server, "url.query"), Some("page=2".into()));
+    assert_eq!(
+        attribute(server, "server.address"),
+        Some("orders.example".into())
+    );
+    assert_eq!(attribute(server, "server.port"), Some(Value::I64(8443)));
+    assert!(matches!(server.status, Status::Error { .. }));
+    assert_eq!(
+        attribute(client, "url.full"),
+        Some("http://orders.internal:8080/v1/orders?page=2".into())
+    );
+    assert_eq!(attribute(client, "server.port"), Some(Value::I64(8080)));
+    assert_eq!(attribute(client, "error.type"), Some("503".into()));
+}